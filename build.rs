@@ -27,11 +27,13 @@ fn find_all_yaml_for_cargo_cache() {
     }
 
     let workdir = workdir.unwrap();
-    let locale_path = format!("{}/**/locales/**/*.yml", workdir);
+    for extension in ["yml", "yaml", "json", "toml", "po", "mo"] {
+        let locale_path = format!("{}/**/locales/**/*.{}", workdir, extension);
 
-    for entry in glob::glob(&locale_path).expect("Failed to read glob pattern") {
-        let entry = entry.unwrap();
-        println!("cargo:rerun-if-changed={}", entry.display());
+        for entry in glob::glob(&locale_path).expect("Failed to read glob pattern") {
+            let entry = entry.unwrap();
+            println!("cargo:rerun-if-changed={}", entry.display());
+        }
     }
 }
 
@@ -35,8 +35,8 @@ static METHOD_NAME: &str = "t";
 pub fn extract(results: &mut Results, path: &PathBuf, source: &str) -> Result<(), Error> {
     let mut ex = Extractor { results, path };
 
-    let file =
-        syn::parse_file(source).expect(&format!("Failed to parse file, file: {}", path.display()));
+    let file = syn::parse_file(source)
+        .unwrap_or_else(|_| panic!("Failed to parse file, file: {}", path.display()));
     let stream = file.into_token_stream();
     ex.invoke(stream)
 }
@@ -63,7 +63,7 @@ impl<'a> Extractor<'a> {
                         }
                     }
 
-                    if (ident == "format_t" || ident == "t") && is_macro {
+                    if (ident == "format_t" || ident == METHOD_NAME) && is_macro {
                         if let Some(TokenTree::Group(group)) = token_iter.peek() {
                             self.take_message(group.stream());
                         }
@@ -22,7 +22,7 @@ pub fn generate<'a, P: AsRef<Path>>(
 
     let old_translations = locales_yaml_files_to_translation_map(&output_path)?;
 
-    let mut new_translations: Translations = HashMap::new();
+    let mut new_translations = Translations::new();
     let mut new_values: HashMap<String, String> = HashMap::new();
 
     for m in messages {
@@ -34,7 +34,7 @@ pub fn generate<'a, P: AsRef<Path>>(
             }
         }
 
-        if old_translations.get(&key).is_some() {
+        if old_translations.contains_key(&key) {
             continue;
         }
 
@@ -64,7 +64,7 @@ pub fn generate<'a, P: AsRef<Path>>(
     Err(Error::SerDe)
 }
 
-fn write_file<'a, P: AsRef<Path>>(
+fn write_file<P: AsRef<Path>>(
     output: &P,
     filename: &str,
     translations: &Translations,
@@ -0,0 +1,137 @@
+//! Process-wide cache of the deserialized translations.
+//!
+//! The proc macro is loaded once per compiler process, so all `format_t!`
+//! invocations of a crate share the translations read here.
+
+use fs_err as fs;
+use once_cell::sync::Lazy;
+use rust_i18n_support::config::{I18nConfig, Layout};
+use rust_i18n_support::plural::PluralCategory;
+use rust_i18n_support::{Conflict, TranslationMap};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Identifies a version of a file without reading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fingerprint {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            path: path.to_owned(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Loaded translations by path of the serialized file or locale directory
+/// and the layout and namespacing of its files, with the fingerprints of all
+/// files they were read from.
+type Cache = HashMap<(PathBuf, Layout, bool), (Vec<Fingerprint>, Arc<Index>)>;
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(Default::default);
+
+/// The cached translations of `key`, unless any of `files` changed since
+/// they were `read`.
+fn cached(
+    key: (PathBuf, Layout, bool),
+    files: &[PathBuf],
+    read: impl FnOnce() -> rust_i18n_support::Result<(TranslationMap, Vec<Conflict>)>,
+) -> rust_i18n_support::Result<Arc<Index>> {
+    let fingerprints = files
+        .iter()
+        .map(|file| Fingerprint::of(file))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, index)) = cache.get(&key) {
+        if *cached == fingerprints {
+            return Ok(Arc::clone(index));
+        }
+    }
+
+    let (tp2trans_per_locale, conflicts) = read()?;
+    let index = Arc::new(Index {
+        conflicts,
+        ..Index::new(tp2trans_per_locale)
+    });
+    cache.insert(key, (fingerprints, Arc::clone(&index)));
+    Ok(index)
+}
+
+/// Load the translations serialized to `path`, compiled from `locales_dir`.
+///
+/// The file is only read again if its size or modification time changed.
+pub(crate) fn load(path: &Path, locales_dir: &Path) -> rust_i18n_support::Result<Arc<Index>> {
+    // The build script already applied the layout.
+    let key = (path.to_owned(), Layout::default(), false);
+    cached(key, &[path.to_owned()], || {
+        let bytes = fs::read(path)?;
+        // Conflicts were reported by the build script.
+        let tp2trans_per_locale = rust_i18n_support::deserialize_fresh(&bytes[..], locales_dir)?;
+        Ok((tp2trans_per_locale, Vec::new()))
+    })
+}
+
+/// Load the translations from the locale files in `locales_dir`, in the layout of `config`.
+///
+/// The files are only read again if any was added, removed or changed.
+pub(crate) fn load_dir(
+    locales_dir: &Path,
+    config: &I18nConfig,
+) -> rust_i18n_support::Result<Arc<Index>> {
+    let files = rust_i18n_support::locale_files(locales_dir)?;
+    let key = (locales_dir.to_owned(), config.layout, config.namespaces);
+    cached(key, &files, || {
+        rust_i18n_support::load_locales(locales_dir, config)
+    })
+}
+
+/// Translations with the lookup structures needed by all invocations.
+pub(crate) struct Index {
+    pub(crate) tp2trans_per_locale: TranslationMap,
+    /// Keys addressing plural forms, `messages` for `messages.one`.
+    pub(crate) plural_parents: BTreeSet<String>,
+    /// Keys of lists, `days` for `days.0`.
+    pub(crate) list_parents: BTreeSet<String>,
+    /// Keys defined differently by several locale files.
+    pub(crate) conflicts: Vec<Conflict>,
+}
+
+impl Index {
+    pub(crate) fn new(tp2trans_per_locale: TranslationMap) -> Self {
+        let plural_parents = tp2trans_per_locale
+            .keys()
+            .filter_map(|key| {
+                let (parent, category) = key.rsplit_once('.')?;
+                PluralCategory::from_key(category).map(|_| parent.to_owned())
+            })
+            .collect();
+        let list_parents = tp2trans_per_locale
+            .keys()
+            .filter_map(|key| key.strip_suffix(".0").map(str::to_owned))
+            .collect();
+        Self {
+            tp2trans_per_locale,
+            plural_parents,
+            list_parents,
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// All keys that can be passed to `format_t!` or `list_t!`.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &str> {
+        self.tp2trans_per_locale
+            .keys()
+            .map(String::as_str)
+            .chain(self.plural_parents.iter().map(String::as_str))
+            .chain(self.list_parents.iter().map(String::as_str))
+    }
+}
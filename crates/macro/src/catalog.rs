@@ -0,0 +1,48 @@
+//! Embed all translations into the binary, for keys only known at runtime.
+
+use crate::cache::Index;
+use proc_macro2::TokenStream;
+use quote::quote;
+use rust_i18n_support::config::I18nConfig;
+use rust_i18n_support::Locale;
+use std::collections::BTreeSet;
+
+/// Generate a `Catalog` expression, sorted for binary search.
+pub(crate) fn embed(index: &Index, config: &I18nConfig, support: &syn::Path) -> TokenStream {
+    let default_locale = config.default_locale.as_str();
+    let (locale, fallbacks): (Vec<_>, Vec<_>) = config
+        .fallbacks
+        .iter()
+        .map(|(locale, fallbacks)| {
+            let fallbacks = fallbacks.iter().map(Locale::as_str).collect::<Vec<_>>();
+            (locale.as_str(), fallbacks)
+        })
+        .unzip();
+    let mut locales = index
+        .tp2trans_per_locale
+        .values()
+        .flat_map(|translations| translations.keys().map(|locale| locale.as_str()))
+        .collect::<BTreeSet<_>>();
+    locales.insert(default_locale);
+    for (locale, fallbacks) in &config.fallbacks {
+        locales.insert(locale.as_str());
+        locales.extend(fallbacks.iter().map(Locale::as_str));
+    }
+    let locales = locales.into_iter();
+    let tp = index.tp2trans_per_locale.keys();
+    let translations = index.tp2trans_per_locale.values().map(|translations| {
+        let (locale, translation): (Vec<_>, Vec<_>) = translations
+            .iter()
+            .map(|(locale, translation)| (locale.as_str(), translation.trim()))
+            .unzip();
+        quote!( &[ #( (#locale, #translation), )* ] )
+    });
+    quote!(
+        #support::Catalog {
+            default_locale: #default_locale,
+            locales: &[ #( #locales ),* ],
+            fallbacks: &[ #( (#locale, &[ #( #fallbacks ),* ]), )* ],
+            translations: &[ #( (#tp, #translations), )* ],
+        }
+    )
+}
@@ -0,0 +1,154 @@
+//! Compile time verification of `format_t!` arguments
+//! against the `%{placeholders}` of all translations.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote_spanned;
+use rust_i18n_support::config::Severity;
+use rust_i18n_support::plural::{self, PluralCategory};
+use rust_i18n_support::template;
+use rust_i18n_support::Conflict;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::translation::{Translation, COUNT};
+use super::{BoundArgs, FormatArg};
+
+/// Verify every named argument is used as placeholder by at least one locale,
+/// and every placeholder of every locale is filled by an argument.
+///
+/// Unknown arguments are always an error, unless they are `known` to be used
+/// otherwise. Unfilled placeholders are reported according to `unfilled`.
+/// Returns the warnings to be emitted as part of the expansion.
+pub(crate) fn placeholders<'a>(
+    tp: &str,
+    translations: &BTreeMap<&str, Translation<'_>>,
+    args: impl IntoIterator<Item = &'a FormatArg>,
+    known: &[&str],
+    unfilled: Severity,
+    span: Span,
+) -> syn::Result<TokenStream> {
+    let mut used = BTreeMap::<&str, BTreeSet<&str>>::new();
+    for (locale, translation) in translations {
+        for template in translation.templates() {
+            for name in template::placeholders(template) {
+                used.entry(name).or_default().insert(locale);
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    let mut filled = BTreeSet::new();
+    for arg in args {
+        let alias = arg.alias();
+        let name = alias.to_string();
+        if !used.contains_key(name.as_str()) && !known.contains(&name.as_str()) {
+            errors.push(syn::Error::new(
+                alias.span(),
+                format!("Unknown argument `{name}`, no translation of \"{tp}\" has a `%{{{name}}}` placeholder"),
+            ));
+        }
+        filled.insert(name);
+    }
+
+    let mut warnings = TokenStream::new();
+    for (name, locales) in used {
+        if filled.contains(name) {
+            continue;
+        }
+        let locales = locales.into_iter().collect::<Vec<_>>().join(", ");
+        let msg = format!(
+            "Placeholder `%{{{name}}}` of \"{tp}\" is not filled by any argument (locales: {locales})"
+        );
+        match unfilled {
+            Severity::Allow => {}
+            Severity::Warn => warnings.extend(warning(&msg, span)),
+            Severity::Deny => errors.push(syn::Error::new(span, msg)),
+        }
+    }
+
+    combine(errors).map(|()| warnings)
+}
+
+/// Verify plural translations have a `count` argument to select the form with,
+/// and provide all the categories the CLDR rules of their locale require.
+pub(crate) fn plural_forms(
+    tp: &str,
+    translations: &BTreeMap<&str, Translation<'_>>,
+    args: &BoundArgs,
+    span: Span,
+) -> syn::Result<()> {
+    let mut errors = Vec::new();
+    if args.get(COUNT).is_none() {
+        errors.push(syn::Error::new(
+            span,
+            format!("\"{tp}\" has plural forms, a `{COUNT}` argument is required to select one"),
+        ));
+    }
+    for (locale, translation) in translations {
+        let Translation::Plural(forms) = translation else {
+            continue;
+        };
+        let missing = plural::categories(locale)
+            .iter()
+            .chain(Some(&PluralCategory::Other))
+            .filter(|category| !forms.contains_key(category))
+            .map(PluralCategory::as_str)
+            .collect::<BTreeSet<_>>();
+        if !missing.is_empty() {
+            let missing = missing.into_iter().collect::<Vec<_>>().join(", ");
+            errors.push(syn::Error::new(
+                span,
+                format!(
+                    "Plural forms of \"{tp}\" in locale `{locale}` lack the categories: {missing}"
+                ),
+            ));
+        }
+    }
+    combine(errors)
+}
+
+/// Report keys that several locale files define differently, as items.
+pub(crate) fn conflicts(
+    conflicts: &[Conflict],
+    severity: Severity,
+    span: Span,
+) -> syn::Result<TokenStream> {
+    let mut warnings = TokenStream::new();
+    let mut errors = Vec::new();
+    for conflict in conflicts {
+        let msg = conflict.to_string();
+        match severity {
+            Severity::Allow => {}
+            Severity::Warn => {
+                let warning = warning(&msg, span);
+                warnings.extend(quote_spanned!(span=> const _: () = #warning;));
+            }
+            Severity::Deny => errors.push(syn::Error::new(span, msg)),
+        }
+    }
+    combine(errors).map(|()| warnings)
+}
+
+fn combine(errors: Vec<syn::Error>) -> syn::Result<()> {
+    match errors.into_iter().reduce(|mut acc, e| {
+        acc.combine(e);
+        acc
+    }) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Stable proc macros can not emit warnings,
+/// so use a deprecated item to make the compiler do it.
+pub(crate) fn warning(msg: &str, span: Span) -> TokenStream {
+    let ident = proc_macro2::Ident::new("rust_i18n_warning", span);
+    quote_spanned!(span=>
+        {
+            #[deprecated(note = #msg)]
+            #[allow(non_upper_case_globals)]
+            const #ident: () = ();
+            #[allow(clippy::let_unit_value)]
+            let _ = #ident;
+        }
+    )
+}
@@ -0,0 +1,90 @@
+//! Resolve locales to the translation to use, following the
+//! `fallbacks` chains of the i18n config.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use rust_i18n_support::config::I18nConfig;
+use rust_i18n_support::Locale;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// For which locales to use which available translation.
+pub(crate) struct Resolution<'a> {
+    /// Translation locale to all known locales that resolve to it,
+    /// including those resolving to `default`: stripping their subtags
+    /// at runtime could reach a locale their chain skips.
+    arms: BTreeMap<&'a str, Vec<&'a str>>,
+    /// The translation locale used for unknown locales.
+    default: Option<&'a str>,
+}
+
+impl<'a> Resolution<'a> {
+    /// Resolve all locales known at compile time, being the locales of
+    /// `available` translations, the configured ones and those with fallbacks.
+    pub(crate) fn new(
+        available: impl IntoIterator<Item = &'a str>,
+        config: &'a I18nConfig,
+    ) -> Self {
+        let available = available.into_iter().collect::<BTreeSet<_>>();
+        let resolve = |locale: &str| {
+            config
+                .fallback_chain(locale)
+                .into_iter()
+                .find_map(|l| available.get(l.as_str()).copied())
+        };
+
+        let default = resolve(&config.default_locale);
+        let known = available
+            .iter()
+            .copied()
+            .chain(config.available_locales.iter().map(Locale::as_str))
+            .chain(config.fallbacks.keys().map(Locale::as_str))
+            .collect::<BTreeSet<_>>();
+
+        let mut arms = BTreeMap::<&str, Vec<&str>>::new();
+        for locale in known {
+            if let Some(source) = resolve(locale) {
+                arms.entry(source).or_default().push(locale);
+            }
+        }
+        Self { arms, default }
+    }
+
+    /// Generate a lookup of the translation for the locale `locale`.
+    ///
+    /// Locales unknown at compile time have their trailing subtags stripped
+    /// at runtime (`en-US` becomes `en`), until a known one remains.
+    pub(crate) fn lookup(
+        &self,
+        locale: TokenStream,
+        render: impl Fn(&str) -> TokenStream,
+        missing: TokenStream,
+    ) -> TokenStream {
+        let fallback = self.default.map(&render).unwrap_or(missing);
+        if self.arms.is_empty() {
+            return fallback;
+        }
+
+        let current = Ident::new("__rust_i18n_locale", Span::mixed_site());
+        let other = Ident::new("__rust_i18n_other", Span::mixed_site());
+        let idx = Ident::new("__rust_i18n_idx", Span::mixed_site());
+        let patterns = self.arms.values().map(|locales| quote!( #( #locales )|* ));
+        let translations = self.arms.keys().map(|source| render(source));
+        quote!(
+            {
+                let mut #current: &str = #locale;
+                loop {
+                    break match #current {
+                        #( #patterns => { #translations }, )*
+                        #other => match #other.rfind('-') {
+                            ::std::option::Option::Some(#idx) => {
+                                #current = &#other[..#idx];
+                                continue;
+                            }
+                            ::std::option::Option::None => { #fallback },
+                        },
+                    };
+                }
+            }
+        )
+    }
+}
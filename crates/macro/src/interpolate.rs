@@ -0,0 +1,93 @@
+//! Compile `%{name}` translation templates into `format!` invocations.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{quote, ToTokens};
+use rust_i18n_support::template::{self, Piece};
+
+use super::FormatArg;
+
+/// The arguments of a single `format_t!` invocation,
+/// each evaluated exactly once, no matter how many
+/// translations refer to it.
+pub(crate) struct BoundArgs {
+    binding: Ident,
+    names: Vec<String>,
+    values: Vec<TokenStream>,
+}
+
+impl BoundArgs {
+    pub(crate) fn new<'a>(args: impl IntoIterator<Item = &'a FormatArg>) -> Self {
+        let (names, values) = args
+            .into_iter()
+            .map(|arg| (arg.alias().to_string(), arg.value()))
+            .unzip();
+        Self {
+            binding: Ident::new("__rust_i18n_args", Span::mixed_site()),
+            names,
+            values,
+        }
+    }
+
+    /// Evaluate all arguments and make them available to `body`.
+    pub(crate) fn bind(&self, body: TokenStream) -> TokenStream {
+        if self.values.is_empty() {
+            return body;
+        }
+        let binding = &self.binding;
+        let values = &self.values;
+        quote!(
+            match ( #( &#values, )* ) {
+                #binding => #body,
+            }
+        )
+    }
+
+    /// The bound arguments as `&[(&str, &dyn Display)]`, for runtime interpolation.
+    pub(crate) fn runtime(&self) -> TokenStream {
+        let names = &self.names;
+        let values = self.names.iter().map(|name| self.get(name));
+        quote!( &[ #( (#names, &#values as &dyn ::std::fmt::Display), )* ] )
+    }
+
+    /// Access the bound argument `name`.
+    pub(crate) fn get(&self, name: &str) -> Option<TokenStream> {
+        let binding = &self.binding;
+        self.names.iter().position(|n| n == name).map(|idx| {
+            let idx = syn::Index::from(idx);
+            quote!( #binding.#idx )
+        })
+    }
+
+    /// Render `translation` with the bound arguments.
+    ///
+    /// Placeholders without a matching argument are kept verbatim.
+    pub(crate) fn format(&self, translation: &str) -> TokenStream {
+        let mut fmt = String::with_capacity(translation.len());
+        let mut values = Vec::new();
+        for piece in template::parse(translation) {
+            match piece {
+                Piece::Text(text) => escape_into(&mut fmt, text),
+                Piece::Placeholder(name) => match self.get(name) {
+                    Some(value) => {
+                        fmt.push_str("{}");
+                        values.push(value);
+                    }
+                    None => escape_into(&mut fmt, &format!("%{{{name}}}")),
+                },
+            }
+        }
+        let fmt = syn::LitStr::new(&fmt, Span::call_site()).into_token_stream();
+        quote!(::std::format!( #fmt #( , #values )* ))
+    }
+}
+
+/// Escape literal text for use in a `format!` string.
+fn escape_into(fmt: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '{' => fmt.push_str("{{"),
+            '}' => fmt.push_str("}}"),
+            c => fmt.push(c),
+        }
+    }
+}
@@ -3,17 +3,23 @@
 //! Required, to filter out `a.b.c` style paths
 //! and avoid erroring for no good reason.
 
-use fs_err as fs;
+use cache::Index;
+use fallback::Resolution;
+use interpolate::BoundArgs;
 use proc_macro2::Ident;
 use proc_macro2::Span;
 use quote::{quote, ToTokens};
-use std::collections::HashMap;
+use rust_i18n_support::config::{self, I18nConfig};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use syn::Token;
 use syn::{parse::Parse, punctuated::Punctuated, Expr};
+use translation::{Translation, COUNT};
 
 /// A single argument as passed to `format!`
 ///
 /// Skips the initial literal string!
+#[allow(clippy::large_enum_variant)]
 enum FormatArg {
     AliasEqIdent {
         alias: Ident,
@@ -52,6 +58,24 @@ impl fmt::Debug for FormatArg {
     }
 }
 
+impl FormatArg {
+    /// Name of the argument, as used in `%{name}` placeholders.
+    fn alias(&self) -> &Ident {
+        match self {
+            Self::Ident { ident } => ident,
+            Self::AliasEqIdent { alias, .. } | Self::AliasEqExpr { alias, .. } => alias,
+        }
+    }
+
+    /// The expression providing the value of the argument.
+    fn value(&self) -> proc_macro2::TokenStream {
+        match self {
+            Self::Ident { ident } | Self::AliasEqIdent { ident, .. } => ident.to_token_stream(),
+            Self::AliasEqExpr { expr, .. } => quote!( (#expr) ),
+        }
+    }
+}
+
 impl syn::parse::Parse for FormatArg {
     fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
         let ident = input.parse()?;
@@ -67,7 +91,17 @@ impl syn::parse::Parse for FormatArg {
                     "Expected `Expr` after = since it's not an ident",
                 )
             })?;
-            Self::AliasEqExpr { alias, eq, expr }
+            match expr {
+                Expr::Path(syn::ExprPath {
+                    ref attrs,
+                    qself: None,
+                    ref path,
+                }) if attrs.is_empty() && path.get_ident().is_some() => {
+                    let ident = path.get_ident().cloned().expect("Checked above. qed");
+                    Self::AliasEqIdent { alias, eq, ident }
+                }
+                expr => Self::AliasEqExpr { alias, eq, expr },
+            }
         } else {
             Self::Ident { ident }
         };
@@ -85,11 +119,55 @@ impl ToTokens for FormatArg {
     }
 }
 
+/// The translation key.
+///
+/// A literal is looked up at compile time,
+/// any other expression at runtime.
+#[allow(clippy::large_enum_variant)]
+enum TranslationKey {
+    Lit(syn::LitStr),
+    Expr(Expr),
+}
+
+impl Parse for TranslationKey {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        match input.parse::<Expr>()? {
+            Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(lit),
+                ..
+            }) => Ok(Self::Lit(lit)),
+            Expr::Lit(syn::ExprLit { lit: other, .. }) => Err(syn::Error::new(
+                other.span(),
+                "Expected a literal str or an expression for the key but found...",
+            )),
+            expr => Ok(Self::Expr(expr)),
+        }
+    }
+}
+
+impl ToTokens for TranslationKey {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self {
+            Self::Lit(lit) => lit.to_tokens(tokens),
+            Self::Expr(expr) => expr.to_tokens(tokens),
+        }
+    }
+}
+
+impl fmt::Debug for TranslationKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lit(lit) => write!(f, r##""{}""##, lit.value()),
+            Self::Expr(_) => f.write_str("<expr>"),
+        }
+    }
+}
+
 /// All format arguments.
 ///
-/// Including the str literal.
+/// Including the key.
 struct FormatArgs {
-    fmt_str: syn::LitStr,
+    key: TranslationKey,
     #[allow(dead_code)]
     maybe_comma: Option<Token![,]>,
     maybe_args: Punctuated<FormatArg, Token![,]>,
@@ -97,32 +175,23 @@ struct FormatArgs {
 
 impl Parse for FormatArgs {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let lit = input.parse::<syn::Lit>()?;
-        let fmt_str = match lit {
-            syn::Lit::Str(alias) => alias,
-            other => {
-                return Err(syn::Error::new(
-                    other.span(),
-                    "Expected a literal str for format arg but found...",
-                ))
-            }
-        };
+        let key = input.parse::<TranslationKey>()?;
         let lookahead = input.lookahead1();
 
         if lookahead.peek(Token![,]) {
             let comma = input.parse::<Token![,]>()?;
 
             let maybe_comma = Some(comma);
-            let maybe_args = Punctuated::<FormatArg, Token![,]>::parse_terminated(&input)?;
+            let maybe_args = Punctuated::<FormatArg, Token![,]>::parse_terminated(input)?;
 
             Ok(Self {
-                fmt_str,
+                key,
                 maybe_comma,
                 maybe_args,
             })
         } else {
             Ok(Self {
-                fmt_str,
+                key,
                 maybe_comma: None,
                 maybe_args: Punctuated::new(),
             })
@@ -133,11 +202,11 @@ impl Parse for FormatArgs {
 impl ToTokens for FormatArgs {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         let FormatArgs {
-            ref fmt_str,
+            ref key,
             maybe_comma,
             ref maybe_args,
         } = self;
-        tokens.extend(fmt_str.to_token_stream());
+        tokens.extend(key.to_token_stream());
         if let Some(comma) = maybe_comma {
             comma.to_tokens(tokens);
             if !maybe_args.is_empty() {
@@ -149,7 +218,7 @@ impl ToTokens for FormatArgs {
 
 impl fmt::Debug for FormatArgs {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, r##""{}""##, self.fmt_str.value())?;
+        write!(f, "{:?}", self.key)?;
         if let Some(_comma) = self.maybe_comma {
             f.write_str(",")?;
             for pair in self.maybe_args.pairs() {
@@ -160,68 +229,314 @@ impl fmt::Debug for FormatArgs {
     }
 }
 
+/// Arguments of the `format_t!` expansion bound to a locale directory.
+struct DirFormatArgs {
+    locales_dir: syn::LitStr,
+    args: FormatArgs,
+}
+
+impl Parse for DirFormatArgs {
+    fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
+        let locales_dir = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let args = input.parse()?;
+        Ok(Self { locales_dir, args })
+    }
+}
+
+/// The config and translations of the crate being compiled, with
+/// locales in `locales_dir` relative to its `Cargo.toml`.
+fn load(locales_dir: &syn::LitStr) -> syn::Result<(I18nConfig, PathBuf, Arc<Index>)> {
+    let manifest_dir = manifest_dir();
+    let config = config::load(&manifest_dir).map_err(|e| {
+        syn::Error::new(
+            Span::call_site(),
+            format!("Failed to load i18n config: {e}"),
+        )
+    })?;
+
+    let locales_dir = manifest_dir.join(locales_dir.value());
+    // Prefer the translations prepared by a build script, if any.
+    let catalog = std::env::var_os(rust_i18n_support::CATALOG_ENV).map(PathBuf::from);
+    let (index, source) = if let Some(path) = &catalog {
+        (cache::load(path, &locales_dir), path)
+    } else {
+        (cache::load_dir(&locales_dir, &config), &locales_dir)
+    };
+    let index = index.map_err(|e| {
+        syn::Error::new(
+            Span::call_site(),
+            format!("Failed to read translations from {}: {e}", source.display()),
+        )
+    })?;
+    Ok((config, locales_dir, index))
+}
+
 fn format_inner(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
     let support = support_crate_path();
+    let DirFormatArgs { locales_dir, args } = syn::parse2::<DirFormatArgs>(input)?;
+    let (config, locales_dir, index) = load(&locales_dir)?;
+    expand(args, &index, &config, &locales_dir, &support)
+}
+
+fn list_inner(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let support = support_crate_path();
+    let DirFormatArgs { locales_dir, args } = syn::parse2::<DirFormatArgs>(input)?;
+    let (_, locales_dir, index) = load(&locales_dir)?;
+    expand_list(args, &index, &locales_dir, &support)
+}
+
+fn i18n_inner(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let support = support_crate_path();
+    let locales_dir = syn::parse2::<syn::LitStr>(input)?;
+    let (config, locales_dir_path, index) = load(&locales_dir)?;
+    let conflicts = check::conflicts(&index.conflicts, config.conflicts, locales_dir.span())?;
+    // Rebuild the crate if a locale file changes. Added files are only
+    // noticed with a build script, which watches the directory.
+    let locale_files = rust_i18n_support::locale_files(&locales_dir_path)
+        .map_err(|e| syn::Error::new(Span::call_site(), e.to_string()))?
+        .into_iter()
+        .map(|file| file.display().to_string());
+
+    let format_t = Ident::new(FORMAT_T, Span::call_site());
+    let list_t = Ident::new(LIST_T, Span::call_site());
+    let catalog_ident = Ident::new(CATALOG, Span::call_site());
+    let catalog = catalog::embed(&index, &config, &support);
+    Ok(quote!(
+        #[doc(hidden)]
+        macro_rules! #format_t {
+            ($($args:tt)*) => {
+                #support::__format_t!(#locales_dir; $($args)*)
+            };
+        }
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #format_t;
+
+        #[doc(hidden)]
+        macro_rules! #list_t {
+            ($($args:tt)*) => {
+                #support::__list_t!(#locales_dir; $($args)*)
+            };
+        }
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #list_t;
+
+        #( const _: &[u8] = include_bytes!(#locale_files); )*
+        #conflicts
+
+        /// All translations, for keys only known at runtime.
+        #[doc(hidden)]
+        #[allow(dead_code)]
+        pub(crate) static #catalog_ident: #support::Catalog = #catalog;
+        // Makes the locales of the catalog known to `set_locale`.
+        #support::__submit! {
+            #support::__RegisteredCatalog(&#catalog_ident)
+        }
+    ))
+}
+
+/// Name of the `format_t!` of a crate, bound to its locale directory by `i18n!`.
+const FORMAT_T: &str = "__rust_i18n_format_t";
+
+/// Name of the `list_t!` of a crate, bound to its locale directory by `i18n!`.
+const LIST_T: &str = "__rust_i18n_list_t";
+
+/// Name of the `Catalog` static emitted by `i18n!`.
+const CATALOG: &str = "__RUST_I18N_CATALOG";
+
+/// Name of the argument to override the current locale with.
+const LOCALE: &str = "locale";
+
+/// Generate the code for a single `format_t!` invocation from the
+/// translations of all locales.
+fn expand(
+    args: FormatArgs,
+    index: &Index,
+    config: &I18nConfig,
+    locales_dir: &Path,
+    support: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
     let FormatArgs {
-        fmt_str,
+        key,
         maybe_comma: _,
         maybe_args,
-    } = syn::parse2(input)?;
-
-    // must be (a.b.c -> (language_2_letter_code -> translation_text)* )*
+    } = args;
 
-    let path = if let Ok(locale_dir) = std::env::var("I18N_LOCALES_SOURCE_DIR") {
-        std::path::PathBuf::from(locale_dir)
-    } else {
-        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("locales")
+    let args = BoundArgs::new(&maybe_args);
+    let locale = locale(&args, support);
+    let fmt_str = match key {
+        TranslationKey::Lit(fmt_str) => fmt_str,
+        TranslationKey::Expr(key) => {
+            let catalog_ident = Ident::new(CATALOG, Span::call_site());
+            let runtime_args = args.runtime();
+            return Ok(args.bind(quote!(
+                crate::#catalog_ident.translate(
+                    #locale,
+                    ::std::convert::AsRef::<str>::as_ref(&#key),
+                    #runtime_args,
+                )
+            )));
+        }
     };
-    path.display();
-    eprintln!("Reading {}", path.display());
-    let bytes = fs::read(&path).unwrap();
-    let tp2trans_per_locale = rust_i18n_support::deserialize(&bytes[..]).unwrap();
-
-    eprintln!("Read {:?}", &tp2trans_per_locale);
-    // Will cause quite a bit of load during compilation for applications with many
-    // invocations, but whatever...
+
     let tp = fmt_str.value();
     let tp = tp.as_str();
-    let translations: &HashMap<String, String> = tp2trans_per_locale.get(tp).ok_or_else(|| {
-        syn::Error::new(
-            Span::call_site(),
-            format!("No translation for \"{tp}\" in {tp2trans_per_locale:?}"),
-        )
-    })?;
+    let translations = Translation::lookup(&index.tp2trans_per_locale, tp);
+    if translations.is_empty() {
+        return Err(suggest::unknown_key(tp, index, locales_dir, fmt_str.span()));
+    }
 
-    let language = translations.keys();
-    let translation = translations.values().map(|v| v.trim().to_owned());
+    let is_plural = translations.values().any(Translation::is_plural);
+    if is_plural {
+        check::plural_forms(tp, &translations, &args, fmt_str.span())?;
+    }
+    let known: &[&str] = if is_plural {
+        &[LOCALE, COUNT]
+    } else {
+        &[LOCALE]
+    };
+    let warnings = check::placeholders(
+        tp,
+        &translations,
+        &maybe_args,
+        known,
+        config.unfilled_placeholders,
+        fmt_str.span(),
+    )?;
+
+    let resolution = Resolution::new(translations.keys().copied(), config);
+    let lookup = args.bind(resolution.lookup(
+        locale,
+        |locale| translations[locale].render(locale, &args, support),
+        quote!(::std::string::String::from(#tp)),
+    ));
     let ts = quote!(
-        match #support::locale() {
-            #( #language => { ::std::format!( #translation, #maybe_args ) }, )*
-            _ => { "<missing translation>".to_owned() }, // TODO FIXME, use a default language
+        {
+            #warnings
+            #lookup
         }
     );
-    println!("{s}", s = ts.to_string());
     Ok(ts)
 }
 
+/// Generate the code for a single `list_t!` invocation, looked up in the
+/// catalog emitted by `i18n!`.
+fn expand_list(
+    args: FormatArgs,
+    index: &Index,
+    locales_dir: &Path,
+    support: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let FormatArgs {
+        key, maybe_args, ..
+    } = args;
+    if let Some(arg) = maybe_args.iter().find(|arg| arg.alias() != LOCALE) {
+        return Err(syn::Error::new(
+            arg.alias().span(),
+            format!("`list_t!` only takes a `{LOCALE}` argument"),
+        ));
+    }
+    if let TranslationKey::Lit(lit) = &key {
+        let tp = lit.value();
+        if !index.list_parents.contains(&tp) {
+            return Err(suggest::unknown_key(&tp, index, locales_dir, lit.span()));
+        }
+    }
+
+    let args = BoundArgs::new(&maybe_args);
+    let locale = locale(&args, support);
+    let catalog_ident = Ident::new(CATALOG, Span::call_site());
+    Ok(args.bind(quote!(
+        crate::#catalog_ident.list(#locale, ::std::convert::AsRef::<str>::as_ref(&#key))
+    )))
+}
+
+/// The locale to translate to, a `locale` argument overrides the current locale.
+///
+/// The argument is canonicalised at runtime, the current locale already is.
+fn locale(args: &BoundArgs, support: &syn::Path) -> proc_macro2::TokenStream {
+    match args.get(LOCALE) {
+        Some(locale) => quote!(
+            &#support::__canonicalize_locale(::std::convert::AsRef::<str>::as_ref(#locale))
+        ),
+        None => quote!(#support::locale()),
+    }
+}
+
+/// Root of the crate currently being compiled.
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo"))
+}
+
+/// Expands to the `format_t!` emitted by `i18n!` at the root of the calling crate.
 #[proc_macro]
 pub fn format_t(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = proc_macro2::TokenStream::from(input);
+    let format_t = Ident::new(FORMAT_T, Span::call_site());
+    quote!( crate::#format_t!(#input) ).into()
+}
+
+/// `format_t!` for the translations in the given locale directory.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __format_t(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     format_inner(proc_macro2::TokenStream::from(input))
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
 
+/// Expands to the `list_t!` emitted by `i18n!` at the root of the calling crate.
+#[proc_macro]
+pub fn list_t(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = proc_macro2::TokenStream::from(input);
+    let list_t = Ident::new(LIST_T, Span::call_site());
+    quote!( crate::#list_t!(#input) ).into()
+}
+
+/// `list_t!` for the translations in the given locale directory.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __list_t(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    list_inner(proc_macro2::TokenStream::from(input))
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Bind the calling crate to the translations in a locale directory.
+#[proc_macro]
+pub fn i18n(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    i18n_inner(proc_macro2::TokenStream::from(input))
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
 fn support_crate_path() -> syn::Path {
     use proc_macro_crate as pmc;
     let found_crate = pmc::crate_name("rust-i18n")
         .expect("rust-i18n must be present in `Cargo.toml`, but it's not");
 
     let ident = match found_crate {
+        // Examples and tests of rust-i18n itself are separate crates.
+        pmc::FoundCrate::Itself
+            if std::env::var("CARGO_CRATE_NAME").as_deref() != Ok("rust_i18n") =>
+        {
+            Ident::new("rust_i18n", Span::call_site())
+        }
         pmc::FoundCrate::Itself => Ident::new("crate", Span::call_site()),
         pmc::FoundCrate::Name(name) => Ident::new(&name, Span::call_site()),
     };
     syn::Path::from(ident)
 }
 
+mod cache;
+mod catalog;
+mod check;
+mod fallback;
+mod interpolate;
+mod suggest;
+mod translation;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,87 @@
+//! Diagnostics for keys without any translation.
+
+use crate::cache::Index;
+use proc_macro2::Span;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// At most that many keys are suggested.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// An error for the unknown key `tp`, suggesting the nearest existing keys
+/// and naming the locale files that were searched.
+pub(crate) fn unknown_key(tp: &str, index: &Index, locales_dir: &Path, span: Span) -> syn::Error {
+    let mut msg = format!("No translation for \"{tp}\"");
+
+    let suggestions = suggestions(tp, index.keys());
+    if !suggestions.is_empty() {
+        let suggestions = suggestions
+            .iter()
+            .map(|key| format!("\"{key}\""))
+            .collect::<Vec<_>>()
+            .join(", ");
+        msg += &format!(", did you mean {suggestions}?");
+    }
+
+    let files = rust_i18n_support::locale_files(locales_dir)
+        .unwrap_or_default()
+        .iter()
+        .map(|file| {
+            file.strip_prefix(locales_dir)
+                .unwrap_or(file)
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    msg += &format!(
+        "\nSearched {} locale files in {}: {}",
+        files.len(),
+        locales_dir.display(),
+        files.join(", ")
+    );
+    syn::Error::new(span, msg)
+}
+
+/// The keys closest to `tp`.
+///
+/// Keys within or containing the subtree `tp` come first,
+/// then keys by edit distance, if it is small enough.
+pub(crate) fn suggestions<'a>(tp: &str, keys: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let max_distance = (tp.chars().count() / 3).max(2);
+    let candidates = keys.into_iter().collect::<BTreeSet<_>>();
+
+    let is_prefix = |a: &str, b: &str| a.strip_prefix(b).is_some_and(|rest| rest.starts_with('.'));
+    let mut scored = candidates
+        .into_iter()
+        .filter_map(|key| {
+            let distance = if is_prefix(key, tp) || is_prefix(tp, key) {
+                0
+            } else {
+                levenshtein(tp, key)
+            };
+            (distance <= max_distance).then_some((distance, key))
+        })
+        .collect::<Vec<_>>();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, key)| key)
+        .collect()
+}
+
+/// Number of single character edits to turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -1,6 +1,7 @@
 use syn::LitStr;
 
 use super::*;
+use rust_i18n_support::config::Severity;
 
 macro_rules! gen_fmtarg_test {
     (pass: $x:expr) => {{
@@ -60,7 +61,7 @@ fn roundtrip_fmtarg_ident_eq_expr() {
 #[test]
 fn roundtrip_fmtargs() {
     let sweed = FormatArgs {
-        fmt_str: LitStr::new("foo bar bay", Span::call_site()),
+        key: TranslationKey::Lit(LitStr::new("foo bar bay", Span::call_site())),
         maybe_comma: Some(Token![,](Span::call_site())),
         maybe_args: {
             let mut p = Punctuated::new();
@@ -94,6 +95,18 @@ fn coll_arg_y() {
     gen_fmtarg_test!(pass: x = y);
 }
 
+#[test]
+fn roundtrip_fmtargs_expr_key() {
+    let sweed = FormatArgs {
+        key: TranslationKey::Expr(syn::parse2::<syn::Expr>(quote! { keys[idx] }).unwrap()),
+        maybe_comma: None,
+        maybe_args: Punctuated::new(),
+    };
+
+    roundtrip!(FormatArgs;
+        sweed);
+}
+
 #[test]
 fn coll_args() {
     gen_fmtargs_test!(pass: "f.b.q");
@@ -102,4 +115,447 @@ fn coll_args() {
     gen_fmtargs_test!(pass: "f.b.q", x,);
     gen_fmtargs_test!(pass: "a.b.c", foo=b, bar);
     gen_fmtargs_test!(pass: "x.y.z", b = { let x = foo?; x }, foo = bar, poo);
+
+    let fmt_args: FormatArgs = syn::parse2(quote::quote! { key, name = name }).unwrap();
+    assert!(matches!(fmt_args.key, TranslationKey::Expr(_)));
+    let fmt_args: FormatArgs = syn::parse2(quote::quote! { &format!("a.{}", x) }).unwrap();
+    assert!(matches!(fmt_args.key, TranslationKey::Expr(_)));
+    assert!(syn::parse2::<FormatArgs>(quote::quote! { 42, x }).is_err());
+}
+
+fn translation_map(entries: &[(&str, &[(&str, &str)])]) -> Index {
+    let tp2trans_per_locale = entries
+        .iter()
+        .map(|(tp, translations)| {
+            let translations = translations
+                .iter()
+                .map(|(locale, translation)| (locale.to_string(), translation.to_string()))
+                .collect();
+            (tp.to_string(), translations)
+        })
+        .collect();
+    Index::new(tp2trans_per_locale)
+}
+
+fn test_locales_dir() -> std::path::PathBuf {
+    std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../tests/locales")
+}
+
+fn expand_with(
+    index: &Index,
+    config: &I18nConfig,
+    input: proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let args = syn::parse2::<FormatArgs>(input).expect("FormatArgs must parse. qed");
+    expand(
+        args,
+        index,
+        config,
+        &test_locales_dir(),
+        &syn::parse_quote!(rust_i18n),
+    )
+}
+
+#[test]
+fn placeholders_match_args() {
+    let tmap = translation_map(&[(
+        "messages.hello",
+        &[("en", "Hello, %{name}!"), ("de", "Hallo, %{name}!")],
+    )]);
+    let config = I18nConfig::default();
+
+    let ts = expand_with(&tmap, &config, quote! { "messages.hello", name = "Jason" }).unwrap();
+    assert!(!ts.to_string().contains("rust_i18n_warning"));
+
+    expand_with(&tmap, &config, quote! { "messages.hello", name }).unwrap();
+}
+
+#[test]
+fn unknown_arg_is_an_error() {
+    let tmap = translation_map(&[("messages.hello", &[("en", "Hello, %{name}!")])]);
+    let config = I18nConfig::default();
+
+    let err = expand_with(&tmap, &config, quote! { "messages.hello", nmae = "Jason" }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unknown argument `nmae`, no translation of \"messages.hello\" has a `%{nmae}` placeholder"
+    );
+}
+
+#[test]
+fn unfilled_placeholder_severity() {
+    let tmap = translation_map(&[(
+        "a.very.nested.message",
+        &[
+            ("en", "Hello, %{name}. Your message is: %{msg}"),
+            ("de", "Hallo, %{name}."),
+        ],
+    )]);
+    let mut config = I18nConfig::default();
+
+    let input = quote! { "a.very.nested.message", name = "Jason" };
+    let ts = expand_with(&tmap, &config, input.clone()).unwrap();
+    assert!(ts.to_string().contains(
+        "Placeholder `%{msg}` of \\\"a.very.nested.message\\\" is not filled by any argument (locales: en)"
+    ));
+
+    config.unfilled_placeholders = Severity::Allow;
+    let ts = expand_with(&tmap, &config, input.clone()).unwrap();
+    assert!(!ts.to_string().contains("rust_i18n_warning"));
+
+    config.unfilled_placeholders = Severity::Deny;
+    let err = expand_with(&tmap, &config, input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Placeholder `%{msg}` of \"a.very.nested.message\" is not filled by any argument (locales: en)"
+    );
+}
+
+#[test]
+fn interpolation_escapes_literal_text() {
+    let tmap = translation_map(&[(
+        "progress",
+        &[("en", "%{name}: {done} 100%% of %{name}, %{unknown}")],
+    )]);
+    let config = I18nConfig {
+        unfilled_placeholders: Severity::Allow,
+        ..I18nConfig::default()
+    };
+
+    let ts = expand_with(&tmap, &config, quote! { "progress", name = user.name() }).unwrap();
+    let ts = ts.to_string();
+    assert!(ts.contains(r#"match (& (user . name ()) ,) { __rust_i18n_args =>"#));
+    assert!(ts.contains(
+        r#":: std :: format ! ("{}: {{done}} 100% of {}, %{{unknown}}" , __rust_i18n_args . 0 , __rust_i18n_args . 0)"#
+    ));
+}
+
+#[test]
+fn fallback_to_default_locale() {
+    let tmap = translation_map(&[(
+        "messages.hello",
+        &[("de", "Hallo, %{name}!"), ("fr", "Bonjour, %{name}!")],
+    )]);
+    let mut config = I18nConfig {
+        default_locale: "fr".parse().unwrap(),
+        ..I18nConfig::default()
+    };
+
+    let ts = expand_with(&tmap, &config, quote! { "messages.hello", name = "Jason" }).unwrap();
+    let ts = ts.to_string();
+    assert!(ts.contains(r#""de" => { :: std :: format ! ("Hallo, {}!" , __rust_i18n_args . 0) }"#));
+    assert!(
+        ts.contains(r#"None => { :: std :: format ! ("Bonjour, {}!" , __rust_i18n_args . 0) }"#)
+    );
+    assert!(
+        ts.contains(r#""fr" => { :: std :: format ! ("Bonjour, {}!" , __rust_i18n_args . 0) }"#)
+    );
+
+    config.default_locale = "en".parse().unwrap();
+    let ts = expand_with(&tmap, &config, quote! { "messages.hello", name = "Jason" }).unwrap();
+    assert!(ts
+        .to_string()
+        .contains(r#"None => { :: std :: string :: String :: from ("messages.hello") }"#));
+}
+
+#[test]
+fn fallback_chains() {
+    let tmap = translation_map(&[(
+        "hello",
+        &[("en", "Hello"), ("zh", "你好"), ("zh-Hant", "妳好")],
+    )]);
+    let config = config::parse(
+        r#"
+        [package.metadata.i18n]
+        available-locales = ["zh-HK", "zh-CN"]
+
+        [package.metadata.i18n.fallbacks]
+        zh-HK = ["zh-Hant"]
+        zh-Hant = ["zh"]
+    "#,
+    )
+    .unwrap();
+
+    let ts = expand_with(&tmap, &config, quote! { "hello" }).unwrap();
+    let ts = ts.to_string();
+    assert!(ts.contains(r#""zh" | "zh-CN" => { :: std :: format ! ("你好") }"#));
+    assert!(ts.contains(r#""zh-HK" | "zh-Hant" => { :: std :: format ! ("妳好") }"#));
+    assert!(ts.contains(r#"None => { :: std :: format ! ("Hello") }"#));
+    assert!(ts.contains(r#""en" => { :: std :: format ! ("Hello") }"#));
+
+    // `pt-BR` falls back to the default `en` before its parent `pt`, at
+    // runtime stripping `pt-BR` to `pt` must not skip that.
+    let tmap = translation_map(&[("hello", &[("en", "Hello"), ("pt", "Olá")])]);
+    let config = config::parse(
+        r#"
+        [package.metadata.i18n.fallbacks]
+        pt-BR = ["en"]
+    "#,
+    )
+    .unwrap();
+    assert_eq!(config.fallback_chain("pt-BR"), ["pt-BR", "en", "pt"]);
+    let ts = expand_with(&tmap, &config, quote! { "hello" }).unwrap();
+    let ts = ts.to_string();
+    assert!(ts.contains(r#""en" | "pt-BR" => { :: std :: format ! ("Hello") }"#));
+    assert!(ts.contains(r#""pt" => { :: std :: format ! ("Olá") }"#));
+}
+
+#[test]
+fn plural_forms_are_selected_by_count() {
+    let tmap = translation_map(&[
+        ("messages.zero", &[("en", "You have no messages.")]),
+        ("messages.one", &[("en", "You have one message.")]),
+        (
+            "messages.other",
+            &[("en", "You have %{count} messages."), ("ja", "%{count}件")],
+        ),
+        ("messages.hello", &[("en", "Hello, %{name}!")]),
+    ]);
+    let config = I18nConfig::default();
+
+    let ts = expand_with(&tmap, &config, quote! { "messages", count = n }).unwrap();
+    let ts = ts.to_string();
+    assert!(ts.contains(
+        "match rust_i18n :: plural :: select (\"ja\" , __rust_i18n_args . 0 , & [rust_i18n :: plural :: PluralCategory :: Other]) { _ => { :: std :: format ! (\"{}件\" , __rust_i18n_args . 0) } , }"
+    ));
+    assert!(ts.contains(
+        "match rust_i18n :: plural :: select (\"en\" , __rust_i18n_args . 0 , & [rust_i18n :: plural :: PluralCategory :: Zero , rust_i18n :: plural :: PluralCategory :: One , rust_i18n :: plural :: PluralCategory :: Other])"
+    ));
+    assert!(ts.contains(
+        "rust_i18n :: plural :: PluralCategory :: Zero => { :: std :: format ! (\"You have no messages.\") }"
+    ));
+
+    let err = expand_with(&tmap, &config, quote! { "messages" }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"messages\" has plural forms, a `count` argument is required to select one"
+    );
+}
+
+#[test]
+fn plural_forms_must_be_complete() {
+    let tmap = translation_map(&[
+        ("apples.one", &[("ru", "%{count} яблоко")]),
+        ("apples.other", &[("en", "%{count} apples")]),
+    ]);
+    let config = I18nConfig::default();
+
+    let err = expand_with(&tmap, &config, quote! { "apples", count = 3 })
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        err,
+        vec![
+            "Plural forms of \"apples\" in locale `en` lack the categories: one",
+            "Plural forms of \"apples\" in locale `ru` lack the categories: few, many, other",
+        ]
+    );
+}
+
+#[test]
+fn expr_key_is_looked_up_at_runtime() {
+    let tmap = translation_map(&[
+        (
+            "messages.hello",
+            &[("en", "Hello, %{name}!"), ("de", "Hallo, %{name}!")],
+        ),
+        ("hello", &[("en", "Hello")]),
+    ]);
+    let mut config = I18nConfig::default();
+    config
+        .fallbacks
+        .insert("de-CH".parse().unwrap(), vec!["de".parse().unwrap()]);
+
+    let catalog = catalog::embed(&tmap, &config, &syn::parse_quote!(rust_i18n));
+    assert_eq!(
+        catalog.to_string(),
+        r#"rust_i18n :: Catalog { default_locale : "en" , locales : & ["de" , "de-CH" , "en"] , fallbacks : & [("de-CH" , & ["de"]) ,] , translations : & [("hello" , & [("en" , "Hello") ,]) , ("messages.hello" , & [("de" , "Hallo, %{name}!") , ("en" , "Hello, %{name}!") ,]) ,] , }"#
+    );
+
+    let ts = expand_with(&tmap, &config, quote! { key, name = name }).unwrap();
+    assert!(ts.to_string().contains(
+        r#"crate :: __RUST_I18N_CATALOG . translate (rust_i18n :: locale () , :: std :: convert :: AsRef :: < str > :: as_ref (& key) , & [("name" , & __rust_i18n_args . 0 as & dyn :: std :: fmt :: Display) ,] ,)"#
+    ));
+}
+
+#[test]
+fn list_keys_are_checked() {
+    let index = translation_map(&[
+        ("date.day_names.0", &[("en", "Sunday")]),
+        ("date.day_names.1", &[("en", "Monday")]),
+    ]);
+    let expand_list = |input| {
+        let args = syn::parse2::<FormatArgs>(input).unwrap();
+        super::expand_list(
+            args,
+            &index,
+            &test_locales_dir(),
+            &syn::parse_quote!(rust_i18n),
+        )
+    };
+
+    let ts = expand_list(quote! { "date.day_names" }).unwrap();
+    assert_eq!(
+        ts.to_string(),
+        r#"crate :: __RUST_I18N_CATALOG . list (rust_i18n :: locale () , :: std :: convert :: AsRef :: < str > :: as_ref (& "date.day_names"))"#
+    );
+    assert!(expand_list(quote! { "date.day_names", locale = "de" }).is_ok());
+
+    let err = expand_list(quote! { "date.day_name" }).unwrap_err();
+    assert!(err.to_string().contains(r#"did you mean "date.day_names""#));
+    let err = expand_list(quote! { "date.day_names", count = 1 }).unwrap_err();
+    assert_eq!(err.to_string(), "`list_t!` only takes a `locale` argument");
+}
+
+#[test]
+fn locale_arg_overrides_current_locale() {
+    let tmap = translation_map(&[(
+        "messages.hello",
+        &[("en", "Hello, %{name}!"), ("de", "Hallo, %{name}!")],
+    )]);
+    let config = I18nConfig::default();
+
+    let ts = expand_with(
+        &tmap,
+        &config,
+        quote! { "messages.hello", locale = &lang.to_string(), name = "Jason" },
+    )
+    .unwrap();
+    let ts = ts.to_string();
+    assert!(
+        ts.contains(r#"match (& (& lang . to_string ()) , & ("Jason") ,) { __rust_i18n_args =>"#)
+    );
+    assert!(ts.contains(
+        "let mut __rust_i18n_locale : & str = & rust_i18n :: __canonicalize_locale (:: std :: convert :: AsRef :: < str > :: as_ref (__rust_i18n_args . 0)) ;"
+    ));
+    assert!(!ts.contains("rust_i18n :: locale ()"));
+
+    let ts = expand_with(&tmap, &config, quote! { key, locale = "de" }).unwrap();
+    assert!(ts.to_string().contains(
+        "translate (& rust_i18n :: __canonicalize_locale (:: std :: convert :: AsRef :: < str > :: as_ref (__rust_i18n_args . 0)) ,"
+    ));
+}
+
+#[test]
+fn unknown_key_suggests_nearest_keys() {
+    let tmap = translation_map(&[
+        ("view.buttons.ok", &[("en", "Ok")]),
+        ("view.buttons.cancel", &[("en", "Cancel")]),
+        (
+            "view.datetime.about_x_hours",
+            &[("en", "about %{count} hours")],
+        ),
+        ("messages.one", &[("en", "One message")]),
+        ("messages.other", &[("en", "%{count} messages")]),
+        ("hello", &[("en", "Hello")]),
+    ]);
+    let config = I18nConfig::default();
+
+    let err = expand_with(&tmap, &config, quote! { "view.button.ok" }).unwrap_err();
+    let locales_dir = test_locales_dir();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "No translation for \"view.button.ok\", did you mean \"view.buttons.ok\"?\n\
+            Searched 3 locale files in {}: de.yml, en.yml, user.en.yml",
+            locales_dir.display()
+        )
+    );
+
+    assert_eq!(
+        suggest::suggestions("view.buttons", tmap.keys()),
+        vec!["view.buttons.cancel", "view.buttons.ok"]
+    );
+    assert_eq!(
+        suggest::suggestions("mesages", tmap.keys()),
+        vec!["messages"]
+    );
+    assert!(suggest::suggestions("goodbye", tmap.keys()).is_empty());
+}
+
+#[test]
+fn catalog_is_cached_until_changed() {
+    let path = std::env::temp_dir().join(format!("rust-i18n-cache-{}", std::process::id()));
+    let locales_dir = test_locales_dir();
+    let content_hash = rust_i18n_support::blob::content_hash(
+        &locales_dir,
+        &rust_i18n_support::locale_files(&locales_dir).unwrap(),
+    )
+    .unwrap();
+    let write = |tmap: &Index, content_hash| {
+        let bytes =
+            rust_i18n_support::serialize(tmap.tp2trans_per_locale.clone(), content_hash).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+    };
+
+    write(
+        &translation_map(&[("hello", &[("en", "Hello")])]),
+        content_hash,
+    );
+    let first = cache::load(&path, &locales_dir).unwrap();
+    let second = cache::load(&path, &locales_dir).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+    assert_eq!(first.keys().collect::<Vec<_>>(), vec!["hello"]);
+
+    write(
+        &translation_map(&[
+            ("hello", &[("en", "Hello")]),
+            ("messages.one", &[("en", "One message")]),
+        ]),
+        content_hash,
+    );
+    let third = cache::load(&path, &locales_dir).unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&first, &third));
+    assert_eq!(
+        third.keys().collect::<Vec<_>>(),
+        vec!["hello", "messages.one", "messages"]
+    );
+
+    write(
+        &translation_map(&[("hello", &[("en", "Hi")])]),
+        !content_hash,
+    );
+    assert!(matches!(
+        cache::load(&path, &locales_dir),
+        Err(rust_i18n_support::Error::Stale { .. })
+    ));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn locale_files_are_loaded_without_build_script() {
+    let index = cache::load_dir(&test_locales_dir(), &I18nConfig::default()).unwrap();
+    assert_eq!(
+        index.tp2trans_per_locale["messages.hello"]["de"],
+        "Hallo, %{name}!"
+    );
+    assert!(std::sync::Arc::ptr_eq(
+        &index,
+        &cache::load_dir(&test_locales_dir(), &I18nConfig::default()).unwrap()
+    ));
+}
+
+#[test]
+fn conflicts_are_reported_by_severity() {
+    let conflicts = [rust_i18n_support::Conflict {
+        key: "en.view.buttons.ok".to_owned(),
+        kind: rust_i18n_support::conflict::ConflictKind::Value,
+        first: PathBuf::from("en.yml"),
+        second: PathBuf::from("view.en.yml"),
+    }];
+    let msg = "`en.view.buttons.ok` is defined in en.yml and view.en.yml with different values, the latter wins";
+
+    let ts = check::conflicts(&conflicts, Severity::Allow, Span::call_site()).unwrap();
+    assert!(ts.is_empty());
+
+    let ts = check::conflicts(&conflicts, Severity::Warn, Span::call_site()).unwrap();
+    let ts = ts.to_string();
+    assert!(ts.starts_with("const _ : () = {"));
+    assert!(ts.contains(&format!("{msg:?}")));
+
+    let err = check::conflicts(&conflicts, Severity::Deny, Span::call_site()).unwrap_err();
+    assert_eq!(err.to_string(), msg);
 }
@@ -0,0 +1,100 @@
+//! The translations of a single key, as plain text or
+//! as CLDR plural forms.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use rust_i18n_support::plural::PluralCategory;
+use rust_i18n_support::TranslationMap;
+use std::collections::BTreeMap;
+
+use super::BoundArgs;
+
+/// Name of the argument to select plural forms with.
+pub(crate) const COUNT: &str = "count";
+
+/// The translation of a key in a single locale.
+pub(crate) enum Translation<'a> {
+    Text(&'a str),
+    /// The key is a subtree with `one`, `other`, etc. children.
+    Plural(BTreeMap<PluralCategory, &'a str>),
+}
+
+impl<'a> Translation<'a> {
+    /// Collect the translations of `tp` per locale.
+    ///
+    /// If `tp` is no translation itself, the plural forms `tp.zero` to `tp.other` are used.
+    pub(crate) fn lookup(
+        tp2trans_per_locale: &'a TranslationMap,
+        tp: &str,
+    ) -> BTreeMap<&'a str, Self> {
+        if let Some(translations) = tp2trans_per_locale.get(tp) {
+            return translations
+                .iter()
+                .map(|(locale, translation)| (locale.as_str(), Self::Text(translation.trim())))
+                .collect();
+        }
+
+        let mut forms = BTreeMap::<&str, BTreeMap<_, _>>::new();
+        for category in PluralCategory::ALL {
+            let Some(translations) = tp2trans_per_locale.get(&format!("{tp}.{category}")) else {
+                continue;
+            };
+            for (locale, translation) in translations {
+                forms
+                    .entry(locale.as_str())
+                    .or_default()
+                    .insert(category, translation.trim());
+            }
+        }
+        forms
+            .into_iter()
+            .map(|(locale, forms)| (locale, Self::Plural(forms)))
+            .collect()
+    }
+
+    pub(crate) fn is_plural(&self) -> bool {
+        matches!(self, Self::Plural(_))
+    }
+
+    /// All templates of this translation.
+    pub(crate) fn templates(&self) -> Vec<&'a str> {
+        match self {
+            Self::Text(translation) => vec![translation],
+            Self::Plural(forms) => forms.values().copied().collect(),
+        }
+    }
+
+    /// Render the translation of `locale` with the bound arguments,
+    /// selecting the plural form by the `count` argument.
+    pub(crate) fn render(
+        &self,
+        locale: &str,
+        args: &BoundArgs,
+        support: &syn::Path,
+    ) -> TokenStream {
+        let forms = match self {
+            Self::Text(translation) => return args.format(translation),
+            Self::Plural(forms) => forms,
+        };
+        let count = args
+            .get(COUNT)
+            .expect("Presence of `count` is checked for plural translations. qed");
+        let category = |category: &PluralCategory| {
+            let variant = Ident::new(&format!("{category:?}"), Span::call_site());
+            quote!( #support::plural::PluralCategory::#variant )
+        };
+        let available = forms.keys().map(category);
+        let (selected, translation): (Vec<_>, Vec<_>) = forms
+            .iter()
+            .filter(|(c, _)| **c != PluralCategory::Other)
+            .map(|(c, translation)| (category(c), args.format(translation)))
+            .unzip();
+        let other = args.format(forms[&PluralCategory::Other]);
+        quote!(
+            match #support::plural::select(#locale, #count, &[ #( #available ),* ]) {
+                #( #selected => { #translation }, )*
+                _ => { #other },
+            }
+        )
+    }
+}
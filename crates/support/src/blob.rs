@@ -0,0 +1,110 @@
+//! The compiled catalog file, written by a build script and read by `format_t!`.
+//!
+//! The file starts with [`MAGIC`], followed by the postcard encoded [`Header`]
+//! and [`TranslationMap`].
+
+use crate::{Error, Result, TranslationMap};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// First bytes of every catalog file.
+pub const MAGIC: &[u8; 8] = b"RI18NCAT";
+
+/// Version of the catalog file format, increased on every incompatible change.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Describes the content of a catalog file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    /// [`content_hash`] of the locale files the catalog was compiled from.
+    pub content_hash: u64,
+    /// All locales with translations, sorted.
+    pub locales: Vec<String>,
+}
+
+/// All locales with translations in `tp2trans_per_locale`, sorted.
+fn locales(tp2trans_per_locale: &TranslationMap) -> Vec<String> {
+    tp2trans_per_locale
+        .values()
+        .flat_map(|translations| translations.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// FNV-1a hash of the names, relative to `locales_dir`, and content of `files`.
+///
+/// Stable across platforms and compiler versions, unlike `std`'s hashers.
+pub fn content_hash(locales_dir: &Path, files: &[impl AsRef<Path>]) -> Result<u64> {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let mut hash = OFFSET_BASIS;
+    let mut update = |bytes: &[u8]| {
+        for byte in bytes.iter().chain(&[0]) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+    for file in files {
+        let file = file.as_ref();
+        let name = file.strip_prefix(locales_dir).unwrap_or(file);
+        update(name.to_string_lossy().as_bytes());
+        update(&fs::read(file)?);
+    }
+    Ok(hash)
+}
+
+pub fn serialize(text2translations: TranslationMap, content_hash: u64) -> Result<Vec<u8>> {
+    let header = Header {
+        version: FORMAT_VERSION,
+        content_hash,
+        locales: locales(&text2translations),
+    };
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(postcard::to_allocvec(&header)?);
+    bytes.extend(postcard::to_allocvec(&text2translations)?);
+    Ok(bytes)
+}
+
+/// Read the header of a catalog, returns it with the remaining bytes.
+pub fn read_header(bytes: &[u8]) -> Result<(Header, &[u8])> {
+    let bytes = bytes.strip_prefix(MAGIC).ok_or(Error::NotACatalog)?;
+    // The version comes first, so it can be checked before anything else is decoded.
+    let (version, _) = postcard::take_from_bytes::<u32>(bytes)?;
+    if version != FORMAT_VERSION {
+        return Err(Error::FormatVersion {
+            found: version,
+            expected: FORMAT_VERSION,
+        });
+    }
+    Ok(postcard::take_from_bytes::<Header>(bytes)?)
+}
+
+pub fn deserialize(bytes: &[u8]) -> Result<TranslationMap> {
+    let (header, bytes) = read_header(bytes)?;
+    let tmap: TranslationMap = postcard::from_bytes(bytes)?;
+    let locales = locales(&tmap);
+    if header.locales != locales {
+        return Err(Error::LocalesMismatch {
+            header: header.locales,
+            found: locales,
+        });
+    }
+    Ok(tmap)
+}
+
+/// Like [`deserialize`], but fails with [`Error::Stale`] if the locale files
+/// in `locales_dir` changed since the catalog was compiled.
+pub fn deserialize_fresh(bytes: &[u8], locales_dir: &Path) -> Result<TranslationMap> {
+    let (header, _) = read_header(bytes)?;
+    let files = crate::locale_files(locales_dir)?;
+    if header.content_hash != content_hash(locales_dir, &files)? {
+        return Err(Error::Stale {
+            locales_dir: locales_dir.to_owned(),
+        });
+    }
+    deserialize(bytes)
+}
@@ -0,0 +1,121 @@
+//! Translations embedded into the binary, for lookups of keys
+//! only known at runtime.
+
+use crate::config;
+use crate::plural::{self, PluralCategory};
+use crate::template;
+use std::fmt;
+
+/// Translations per locale, by key.
+///
+/// Generated by `i18n!` as `static`, all slices are sorted by key resp. locale.
+#[derive(Debug)]
+pub struct Catalog {
+    pub default_locale: &'static str,
+    /// The locales with translations, the default locale and those of the
+    /// declared fallbacks.
+    pub locales: &'static [&'static str],
+    pub fallbacks: &'static [(&'static str, &'static [&'static str])],
+    pub translations: &'static [(&'static str, &'static [(&'static str, &'static str)])],
+}
+
+impl Catalog {
+    /// The declared fallbacks of `locale`.
+    fn fallbacks(&self, locale: &str) -> impl Iterator<Item = &'static str> {
+        self.fallbacks
+            .binary_search_by_key(&locale, |(l, _)| *l)
+            .map(|idx| self.fallbacks[idx].1)
+            .unwrap_or_default()
+            .iter()
+            .copied()
+    }
+
+    /// All locales to look up a translation for `locale` in, by priority.
+    pub fn fallback_chain(&self, locale: &str) -> Vec<String> {
+        config::fallback_chain(locale, self.default_locale, |locale| self.fallbacks(locale))
+    }
+
+    /// The first of [`Self::locales`] in the fallback chain of `locale`,
+    /// not counting the default locale, `en` for `en-US` if there is no `en-US`.
+    pub fn resolve_locale(&self, locale: &str) -> Option<&'static str> {
+        config::locale_chain(locale, |locale| self.fallbacks(locale))
+            .iter()
+            .find_map(|locale| {
+                let idx = self.locales.binary_search(&locale.as_str()).ok()?;
+                Some(self.locales[idx])
+            })
+    }
+
+    fn get(&self, locale: &str, key: &str) -> Option<&'static str> {
+        let idx = self
+            .translations
+            .binary_search_by_key(&key, |(k, _)| *k)
+            .ok()?;
+        let translations = self.translations[idx].1;
+        let idx = translations
+            .binary_search_by_key(&locale, |(l, _)| *l)
+            .ok()?;
+        Some(translations[idx].1)
+    }
+
+    /// The translation of `key` for `locale`, following the fallback chain.
+    pub fn lookup(&self, locale: &str, key: &str) -> Option<&'static str> {
+        self.fallback_chain(locale)
+            .iter()
+            .find_map(|locale| self.get(locale, key))
+    }
+
+    /// The plural forms `key.zero` to `key.other` of the first locale in the
+    /// fallback chain of `locale` that has any.
+    fn plural_forms(
+        &self,
+        locale: &str,
+        key: &str,
+    ) -> Option<(String, Vec<(PluralCategory, &'static str)>)> {
+        self.fallback_chain(locale).into_iter().find_map(|locale| {
+            let forms = PluralCategory::ALL
+                .into_iter()
+                .filter_map(|category| {
+                    self.get(&locale, &format!("{key}.{category}"))
+                        .map(|translation| (category, translation))
+                })
+                .collect::<Vec<_>>();
+            (!forms.is_empty()).then_some((locale, forms))
+        })
+    }
+
+    /// The items `key.0`, `key.1`, .. of the list at `key`, from the first
+    /// locale in the fallback chain of `locale` that has any.
+    ///
+    /// Returns an empty list if there is no translation.
+    pub fn list(&self, locale: &str, key: &str) -> Vec<&'static str> {
+        self.fallback_chain(locale)
+            .iter()
+            .find_map(|locale| {
+                let items = (0..)
+                    .map_while(|idx| self.get(locale, &format!("{key}.{idx}")))
+                    .collect::<Vec<_>>();
+                (!items.is_empty()).then_some(items)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Render the translation of `key` for `locale` with `args`.
+    ///
+    /// If `key` has plural forms, the one for the `count` argument is used.
+    /// Returns the `key` itself if there is no translation.
+    pub fn translate(&self, locale: &str, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        if let Some(translation) = self.lookup(locale, key) {
+            return template::interpolate(translation, args);
+        }
+        let count = args.iter().find(|(name, _)| *name == "count");
+        if let (Some((_, count)), Some((locale, forms))) = (count, self.plural_forms(locale, key)) {
+            let available = forms.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+            let category = plural::select(&locale, &count.to_string(), &available);
+            if let Some((_, translation)) = forms.iter().find(|(c, _)| *c == category) {
+                return template::interpolate(translation, args);
+            }
+        }
+        key.to_owned()
+    }
+}
@@ -0,0 +1,342 @@
+//! This module defines `struct`s that can be deserialized with Serde
+//! to load and inspect `Cargo.toml` metadata.
+//!
+//! See `load` and `parse`.
+use crate::Locale;
+use fs_err as fs;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct I18nConfig {
+    #[serde(default = "default_locale")]
+    pub default_locale: Locale,
+    #[serde(default = "available_locales")]
+    pub available_locales: Vec<Locale>,
+    #[serde(default = "load_path")]
+    pub load_path: String,
+    /// How to report `%{placeholders}` that no `format_t!` argument fills.
+    #[serde(default)]
+    pub unfilled_placeholders: Severity,
+    /// How to report keys that several locale files define differently.
+    #[serde(default)]
+    pub conflicts: Severity,
+    /// Where locale files declare their locale.
+    #[serde(default)]
+    pub layout: Layout,
+    /// Prefix the keys of each file by its name, `views.title` for `title`
+    /// in `de/views.yml`. Only with `layout = "path"`.
+    #[serde(default)]
+    pub namespaces: bool,
+    /// Locales to look up missing translations in, per locale.
+    ///
+    /// i.e. `zh-HK = ["zh-Hant", "zh"]`
+    #[serde(default)]
+    pub fallbacks: BTreeMap<Locale, Vec<Locale>>,
+}
+
+/// How severe a diagnostic emitted at compile time is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Silently accept.
+    Allow,
+    /// Emit a compiler warning.
+    #[default]
+    Warn,
+    /// Fail compilation.
+    Deny,
+}
+
+/// Where locale files declare their locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// The root keys of every file are locales, `en: { hello: Hello }`.
+    #[default]
+    LocaleKey,
+    /// Files only contain translations, the locale comes from their path,
+    /// `de/views.yml` or `views.de.yml`. Every file needs one of both.
+    Path,
+}
+
+fn default_locale() -> Locale {
+    I18nConfig::default().default_locale
+}
+
+fn available_locales() -> Vec<Locale> {
+    I18nConfig::default().available_locales
+}
+
+fn load_path() -> String {
+    I18nConfig::default().load_path
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct MainConfig {
+    pub i18n: I18nConfig,
+}
+
+impl Default for I18nConfig {
+    fn default() -> Self {
+        I18nConfig {
+            default_locale: en(),
+            available_locales: vec![en()],
+            load_path: "./locales".to_string(),
+            unfilled_placeholders: Severity::default(),
+            conflicts: Severity::default(),
+            layout: Layout::default(),
+            namespaces: false,
+            fallbacks: BTreeMap::new(),
+        }
+    }
+}
+
+fn en() -> Locale {
+    Locale::parse("en").expect("`en` is a valid locale")
+}
+
+impl I18nConfig {
+    /// All locales to look up a translation for `locale` in, by priority.
+    ///
+    /// See [`fallback_chain`].
+    pub fn fallback_chain(&self, locale: &str) -> Vec<String> {
+        fallback_chain(locale, &self.default_locale, |locale| {
+            self.fallbacks
+                .get(locale)
+                .into_iter()
+                .flatten()
+                .map(Locale::as_str)
+        })
+    }
+}
+
+/// All locales to look up a translation for `locale` in, by priority.
+///
+/// Starts with `locale` and its declared `fallbacks`, recursively. Then
+/// the trailing subtags of those are stripped one at a time (`en-US` becomes `en`)
+/// and the `default_locale` comes last.
+pub fn fallback_chain<'a, I>(
+    locale: &str,
+    default_locale: &str,
+    fallbacks: impl Fn(&str) -> I,
+) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut chain = locale_chain(locale, &fallbacks);
+    visit(default_locale, &mut chain, &fallbacks);
+    chain
+}
+
+/// The [`fallback_chain`] of `locale` without the default locale.
+pub fn locale_chain<'a, I>(locale: &str, fallbacks: impl Fn(&str) -> I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut chain = Vec::new();
+    visit(locale, &mut chain, &fallbacks);
+    for idx in 0..chain.len() {
+        let mut parent = chain[idx].clone();
+        while let Some(end) = parent.rfind('-') {
+            parent.truncate(end);
+            visit(&parent, &mut chain, &fallbacks);
+        }
+    }
+    chain
+}
+
+/// Append `locale` and its declared `fallbacks` to `chain`, recursively.
+fn visit<'a, I>(locale: &str, chain: &mut Vec<String>, fallbacks: &impl Fn(&str) -> I)
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    if chain.iter().any(|l| l == locale) {
+        return;
+    }
+    chain.push(locale.to_owned());
+    for fallback in fallbacks(locale) {
+        visit(fallback, chain, fallbacks);
+    }
+}
+
+pub fn load(cargo_root: &Path) -> io::Result<I18nConfig> {
+    let cargo_file = cargo_root.join("Cargo.toml");
+    let mut file = fs::File::open(&cargo_file)
+        .unwrap_or_else(|e| panic!("Fail to open {}, {}", cargo_file.display(), e));
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    parse(&contents)
+}
+
+pub fn parse(contents: &str) -> io::Result<I18nConfig> {
+    if !contents.contains("[i18n") && !contents.contains("[package.metadata.i18n") {
+        return Ok(I18nConfig::default());
+    }
+    // Also covers sub tables like `[package.metadata.i18n.fallbacks]`
+    let contents = contents.replace("[package.metadata.i18n", "[i18n");
+    let mut config: MainConfig = toml::from_str(&contents)?;
+
+    // Push default_locale
+    config
+        .i18n
+        .available_locales
+        .insert(0, config.i18n.default_locale.clone());
+
+    // unqiue
+    config.i18n.available_locales = config.i18n.available_locales.into_iter().unique().collect();
+
+    Ok(config.i18n)
+}
+
+#[test]
+fn test_parse() {
+    let contents = r#"
+        [i18n]
+        default-locale = "en"
+        available-locales = ["zh-CN"]
+        load-path = "./my-locales"
+    "#;
+
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.default_locale, "en");
+    assert_eq!(cfg.available_locales, vec!["en", "zh-CN"]);
+    assert_eq!(cfg.load_path, "./my-locales");
+
+    let contents = r#"
+        [i18n]
+        available-locales = ["zh-CN", "de", "de"]
+        load-path = "./my-locales"
+    "#;
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.default_locale, "en");
+    assert_eq!(cfg.available_locales, vec!["en", "zh-CN", "de"]);
+    assert_eq!(cfg.load_path, "./my-locales");
+
+    let contents = "";
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.default_locale, "en");
+    assert_eq!(cfg.available_locales, vec!["en"]);
+    assert_eq!(cfg.load_path, "./locales");
+    assert_eq!(cfg.unfilled_placeholders, Severity::Warn);
+    assert_eq!(cfg.layout, Layout::LocaleKey);
+    assert!(!cfg.namespaces);
+}
+
+#[test]
+fn test_parse_with_metadata() {
+    let contents = r#"
+    [package.metadata.i18n]
+    default-locale = "en"
+    available-locales = ["zh-CN"]
+    load-path = "./my-locales"
+"#;
+
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.default_locale, "en");
+    assert_eq!(cfg.available_locales, vec!["en", "zh-CN"]);
+    assert_eq!(cfg.load_path, "./my-locales");
+}
+
+#[test]
+fn test_parse_unfilled_placeholders() {
+    let contents = r#"
+    [package.metadata.i18n]
+    unfilled-placeholders = "deny"
+    conflicts = "allow"
+"#;
+
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.unfilled_placeholders, Severity::Deny);
+    assert_eq!(cfg.conflicts, Severity::Allow);
+}
+
+#[test]
+fn test_parse_layout() {
+    let contents = r#"
+    [package.metadata.i18n]
+    layout = "path"
+    namespaces = true
+"#;
+
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.layout, Layout::Path);
+    assert!(cfg.namespaces);
+}
+
+#[test]
+fn test_parse_locales() {
+    let contents = r#"
+    [package.metadata.i18n]
+    default-locale = "en_us"
+    available-locales = ["zh_CN", "zh-cn", "en-US"]
+
+    [package.metadata.i18n.fallbacks]
+    zh_HK = ["zh_hant"]
+"#;
+
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.default_locale, "en-US");
+    assert_eq!(cfg.available_locales, vec!["en-US", "zh-CN"]);
+    assert_eq!(cfg.fallbacks["zh-HK"], vec!["zh-Hant"]);
+
+    let contents = r#"
+    [package.metadata.i18n]
+    available-locales = ["zh CN"]
+"#;
+    let err = parse(contents).unwrap_err();
+    assert!(err.to_string().contains("Invalid locale `zh CN`"), "{err}");
+}
+
+#[test]
+fn test_parse_fallbacks() {
+    let contents = r#"
+    [package.metadata.i18n]
+    default-locale = "en"
+
+    [package.metadata.i18n.fallbacks]
+    zh-HK = ["zh-Hant"]
+    zh-Hant = ["zh"]
+"#;
+
+    let cfg = parse(contents).unwrap();
+    assert_eq!(cfg.fallbacks["zh-HK"], vec!["zh-Hant"]);
+    assert_eq!(
+        cfg.fallback_chain("zh-HK"),
+        vec!["zh-HK", "zh-Hant", "zh", "en"]
+    );
+    assert_eq!(cfg.fallback_chain("en-US"), vec!["en-US", "en"]);
+    assert_eq!(
+        cfg.fallback_chain("zh-Hant-TW"),
+        vec!["zh-Hant-TW", "zh-Hant", "zh", "en"]
+    );
+    assert_eq!(cfg.fallback_chain("en"), vec!["en"]);
+}
+
+#[test]
+fn test_load_default() {
+    let workdir = Path::new(env!["CARGO_MANIFEST_DIR"]).join("../..");
+
+    let cfg = load(&workdir).unwrap();
+    assert_eq!(cfg.default_locale, "en");
+    assert_eq!(cfg.available_locales, vec!["en"]);
+    assert_eq!(cfg.load_path, "./locales");
+}
+
+#[test]
+fn test_load() {
+    let workdir = Path::new(env!["CARGO_MANIFEST_DIR"]).join("../..");
+    let cargo_root = workdir.join("examples/foo");
+
+    let cfg = load(&cargo_root).unwrap();
+    assert_eq!(cfg.default_locale, "en");
+    assert_eq!(cfg.available_locales, vec!["en", "zh-CN"]);
+}
@@ -0,0 +1,108 @@
+//! Keys defined differently by several locale files.
+//!
+//! Files are merged in glob order, so which definition wins is an accident
+//! of their names.
+
+use crate::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How two definitions of a key collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both are translations, with different text.
+    Value,
+    /// One is a translation, the other has nested keys.
+    LeafAndSubtree,
+}
+
+/// A key, including its locale, defined in `first` and again in `second`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: String,
+    pub kind: ConflictKind,
+    pub first: PathBuf,
+    pub second: PathBuf,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            ConflictKind::Value => "with different values",
+            ConflictKind::LeafAndSubtree => "as translation and as parent of nested keys",
+        };
+        write!(
+            f,
+            "`{}` is defined in {} and {} {}, the latter wins",
+            self.key,
+            self.first.display(),
+            self.second.display(),
+            what
+        )
+    }
+}
+
+/// Where each key was defined, to find conflicts while merging files.
+#[derive(Debug, Default)]
+pub(crate) struct Origins {
+    leaves: BTreeMap<String, (PathBuf, Value)>,
+    subtrees: BTreeMap<String, PathBuf>,
+    pub(crate) conflicts: Vec<Conflict>,
+}
+
+impl Origins {
+    /// The file defining `key`, or the list or subtree containing it.
+    pub(crate) fn file(&self, key: &str) -> Option<PathBuf> {
+        let mut key = key;
+        loop {
+            if let Some((path, _)) = self.leaves.get(key) {
+                return Some(path.clone());
+            }
+            if let Some(path) = self.subtrees.get(key) {
+                return Some(path.clone());
+            }
+            key = key.rsplit_once('.')?.0;
+        }
+    }
+
+    /// Record the definitions of `value` at `key` in `path`.
+    pub(crate) fn visit(&mut self, path: &Path, key: &str, value: &Value) {
+        let mut conflict = |kind, first: &Path| {
+            self.conflicts.push(Conflict {
+                key: key.to_owned(),
+                kind,
+                first: first.to_owned(),
+                second: path.to_owned(),
+            })
+        };
+        match value {
+            Value::Object(children) => {
+                if let Some((first, _)) = self.leaves.remove(key) {
+                    conflict(ConflictKind::LeafAndSubtree, &first);
+                }
+                self.subtrees
+                    .entry(key.to_owned())
+                    .or_insert_with(|| path.to_owned());
+                for (child, value) in children {
+                    self.visit(path, &format!("{key}.{child}"), value);
+                }
+            }
+            value => {
+                if let Some(first) = self.subtrees.remove(key) {
+                    conflict(ConflictKind::LeafAndSubtree, &first);
+                    // The subtree is replaced as a whole.
+                    let prefix = format!("{key}.");
+                    self.leaves.retain(|k, _| !k.starts_with(&prefix));
+                    self.subtrees.retain(|k, _| !k.starts_with(&prefix));
+                }
+                match self.leaves.get(key) {
+                    Some((first, old)) if old != value => conflict(ConflictKind::Value, first),
+                    _ => {}
+                }
+                self.leaves
+                    .insert(key.to_owned(), (path.to_owned(), value.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! File formats of locale files.
+//!
+//! All formats are parsed into the same [`Translations`], a mapping of
+//! locales to nested keys, so they can be mixed within one locale directory.
+
+use crate::{gettext, Error, Translations, Value};
+use std::path::Path;
+
+/// A locale file format.
+pub trait Format: Sync {
+    /// File extensions of the format, without the leading dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Parse the `content` of the file at `path`.
+    fn parse(&self, path: &Path, content: &[u8]) -> crate::Result<Translations>;
+
+    /// Whether files name their locale themselves, whatever the
+    /// configured [`Layout`](crate::config::Layout).
+    fn names_locale(&self) -> bool {
+        false
+    }
+}
+
+/// YAML, `.yml` and `.yaml`.
+pub struct Yaml;
+
+impl Format for Yaml {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["yml", "yaml"]
+    }
+
+    fn parse(&self, path: &Path, content: &[u8]) -> crate::Result<Translations> {
+        let mut value: Value = serde_yaml::from_slice(content).map_err(|source| {
+            let location = source.location();
+            parse_error(
+                path,
+                location.as_ref().map(|l| (l.line(), l.column())),
+                source,
+            )
+        })?;
+        merge_keys(&mut value);
+        serde_json::from_value(value).map_err(|source| parse_error(path, None, source))
+    }
+}
+
+/// Apply the YAML merge keys `<<: *anchor` in `value`, which `serde_yaml` keeps
+/// as ordinary keys. Keys of the mapping itself take precedence over merged ones,
+/// and earlier mappings of a `<<: [*a, *b]` sequence over later ones.
+fn merge_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if let Some(merged) = map.remove("<<") {
+                let merged = match merged {
+                    Value::Array(items) => items,
+                    merged => vec![merged],
+                };
+                for item in merged {
+                    if let Value::Object(item) = item {
+                        for (key, value) in item {
+                            map.entry(key).or_insert(value);
+                        }
+                    }
+                }
+            }
+            map.values_mut().for_each(merge_keys);
+        }
+        Value::Array(items) => items.iter_mut().for_each(merge_keys),
+        _ => {}
+    }
+}
+
+/// JSON, `.json`.
+pub struct Json;
+
+impl Format for Json {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn parse(&self, path: &Path, content: &[u8]) -> crate::Result<Translations> {
+        serde_json::from_slice(content).map_err(|source| {
+            let location = (source.line() > 0).then(|| (source.line(), source.column()));
+            parse_error(path, location, source)
+        })
+    }
+}
+
+/// TOML, `.toml`.
+pub struct Toml;
+
+impl Format for Toml {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["toml"]
+    }
+
+    fn parse(&self, path: &Path, content: &[u8]) -> crate::Result<Translations> {
+        toml::from_slice(content).map_err(|source| {
+            // `toml` counts from 0.
+            let location = source
+                .line_col()
+                .map(|(line, column)| (line + 1, column + 1));
+            parse_error(path, location, source)
+        })
+    }
+}
+
+/// All supported formats.
+pub static FORMATS: &[&dyn Format] = &[&Yaml, &Json, &Toml, &gettext::Po, &gettext::Mo];
+
+/// The format of the file at `path`, by its extension.
+pub fn for_path(path: &Path) -> Option<&'static dyn Format> {
+    let extension = path.extension()?.to_str()?;
+    FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.extensions().contains(&extension))
+}
+
+pub(crate) fn parse_error(
+    path: &Path,
+    location: Option<(usize, usize)>,
+    source: impl std::error::Error + Send + Sync + 'static,
+) -> Error {
+    Error::Parse {
+        path: path.to_owned(),
+        line: location.map(|(line, _)| line),
+        column: location.map(|(_, column)| column),
+        source: Box::new(source),
+    }
+}
@@ -0,0 +1,573 @@
+//! GNU gettext catalogs, `.po` and compiled `.mo` files.
+//!
+//! Each message becomes a translation keyed by its `msgid`, below its
+//! `msgctxt` if it has one. Plural forms become the plural subkeys of the
+//! `msgid`: each CLDR category integers of the locale fall into gets the
+//! form the `plural` expression of the `Plural-Forms` header selects for
+//! an integer of that category, so French catalogs with `nplurals=2` give
+//! `many` the form of `other`. Categories only decimals fall into, like
+//! `other` in Russian, get the last form. Untranslated and fuzzy messages
+//! are skipped, as `gettext` does.
+//!
+//! The locale is taken from the `Language` header, or else from the path,
+//! `de/LC_MESSAGES/app.po` or `de.po`.
+
+use crate::format::{parse_error, Format};
+use crate::plural::{categories, category, integer_categories, PluralCategory, PluralOperand};
+use crate::{Translations, Value};
+use std::fmt;
+use std::path::Path;
+
+/// Gettext source catalogs, `.po`.
+pub struct Po;
+
+impl Format for Po {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["po"]
+    }
+
+    fn parse(&self, path: &Path, content: &[u8]) -> crate::Result<Translations> {
+        let content =
+            std::str::from_utf8(content).map_err(|source| parse_error(path, None, source))?;
+        let messages =
+            parse_po(content).map_err(|(line, error)| parse_error(path, Some((line, 1)), error))?;
+        translations(path, messages)
+    }
+
+    fn names_locale(&self) -> bool {
+        true
+    }
+}
+
+/// Compiled gettext catalogs, `.mo`.
+pub struct Mo;
+
+impl Format for Mo {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mo"]
+    }
+
+    fn parse(&self, path: &Path, content: &[u8]) -> crate::Result<Translations> {
+        let messages = parse_mo(content).map_err(|error| parse_error(path, None, error))?;
+        translations(path, messages)
+    }
+
+    fn names_locale(&self) -> bool {
+        true
+    }
+}
+
+/// Error in the syntax of a gettext catalog.
+#[derive(Debug)]
+pub struct SyntaxError(String);
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+fn syntax_error(message: impl Into<String>) -> SyntaxError {
+    SyntaxError(message.into())
+}
+
+/// A message of a catalog. The header is the message with an empty `msgid`.
+#[derive(Debug, Default)]
+struct Message {
+    context: Option<String>,
+    id: String,
+    plural: Option<String>,
+    /// The translation, or one per plural form.
+    strs: Vec<String>,
+    fuzzy: bool,
+}
+
+/// Build the translations of the `messages` of the catalog at `path`.
+fn translations(path: &Path, messages: Vec<Message>) -> crate::Result<Translations> {
+    let header = messages
+        .iter()
+        .find(|message| message.id.is_empty() && message.context.is_none())
+        .and_then(|message| message.strs.first());
+    let locale = header
+        .and_then(|header| header_field(header, "Language"))
+        .or_else(|| locale_from_path(path))
+        .unwrap_or_default();
+
+    let forms = if messages.iter().any(|message| message.plural.is_some()) {
+        form_indices(&locale, header.map_or("", String::as_str))
+            .map_err(|error| parse_error(path, None, error))?
+    } else {
+        Vec::new()
+    };
+
+    let mut root = serde_json::Map::new();
+    for message in messages {
+        if message.id.is_empty() || message.fuzzy || message.strs.iter().all(String::is_empty) {
+            continue;
+        }
+        let parent = match message.context {
+            Some(context) => match root
+                .entry(context.clone())
+                .or_insert_with(|| Value::Object(Default::default()))
+            {
+                Value::Object(map) => map,
+                _ => return Err(context_conflict(path, &context)),
+            },
+            None => &mut root,
+        };
+        let value = if message.plural.is_some() {
+            plural_forms(&locale, &forms, message.strs)
+        } else {
+            Value::String(message.strs.into_iter().next().unwrap_or_default())
+        };
+        if parent.get(&message.id).is_some_and(Value::is_object) {
+            return Err(context_conflict(path, &message.id));
+        }
+        parent.insert(message.id, value);
+    }
+    Ok(Translations::from([(locale, Value::Object(root))]))
+}
+
+/// The error for a `msgctxt` that is also the `msgid` of a message without context.
+fn context_conflict(path: &Path, context: &str) -> crate::Error {
+    let message = format!("`msgctxt \"{context}\"` is also a `msgid` without context");
+    parse_error(path, None, syntax_error(message))
+}
+
+/// The plural subkeys of the translated plural `forms`, given the index of
+/// the form of each category integers fall into.
+fn plural_forms(locale: &str, indices: &[(PluralCategory, usize)], forms: Vec<String>) -> Value {
+    let mut map = serde_json::Map::new();
+    for (category, index) in indices {
+        if let Some(form) = forms.get(*index) {
+            map.insert(category.as_str().to_owned(), Value::String(form.clone()));
+        }
+    }
+    if let Some(last) = forms.last() {
+        let integer = integer_categories(locale);
+        for category in categories(locale).iter().filter(|c| !integer.contains(c)) {
+            map.insert(category.as_str().to_owned(), Value::String(last.clone()));
+        }
+    }
+    Value::Object(map)
+}
+
+/// The index of the plural form for each category integers of `locale` fall
+/// into, as selected by the `Plural-Forms` of the catalog `header` for the
+/// first integer of the category. Without `Plural-Forms`, `gettext` assumes
+/// `nplurals=2; plural=(n != 1);`.
+fn form_indices(locale: &str, header: &str) -> Result<Vec<(PluralCategory, usize)>, SyntaxError> {
+    let (nplurals, plural) = match header_field(header, "Plural-Forms") {
+        Some(plural_forms) => parse_plural_forms(&plural_forms)
+            .ok_or_else(|| syntax_error(format!("Invalid Plural-Forms `{plural_forms}`")))?,
+        None => (
+            2,
+            Expr::Binary(Op::Ne, Box::new(Expr::N), Box::new(Expr::Number(1))),
+        ),
+    };
+    // Millions for the `many` of French, Spanish and Italian.
+    let samples = (0..=1000u64).chain([1_000_000]);
+    let mut indices = Vec::new();
+    for category_of_integers in integer_categories(locale) {
+        let Some(n) = samples.clone().find(|n| {
+            n.operands()
+                .is_some_and(|operands| category(locale, &operands) == *category_of_integers)
+        }) else {
+            continue;
+        };
+        let index = plural
+            .eval(n)
+            .and_then(|index| usize::try_from(index).ok())
+            .filter(|index| *index < nplurals)
+            .ok_or_else(|| {
+                syntax_error(format!(
+                    "Plural-Forms has nplurals={nplurals}, but its plural expression has no form for n = {n}"
+                ))
+            })?;
+        indices.push((*category_of_integers, index));
+    }
+    Ok(indices)
+}
+
+/// The value of the field `name` of a catalog header.
+fn header_field(header: &str, name: &str) -> Option<String> {
+    header.lines().find_map(|line| {
+        let (field, value) = line.split_once(':')?;
+        let value = value.trim();
+        (field.trim().eq_ignore_ascii_case(name) && !value.is_empty()).then(|| value.to_owned())
+    })
+}
+
+/// The `nplurals` and `plural` expression of a `Plural-Forms` header field.
+fn parse_plural_forms(plural_forms: &str) -> Option<(usize, Expr)> {
+    let (mut nplurals, mut plural) = (None, None);
+    for part in plural_forms.split(';') {
+        let Some((name, value)) = part.split_once('=') else {
+            continue;
+        };
+        match name.trim() {
+            "nplurals" => nplurals = Some(value.trim().parse().ok()?),
+            "plural" => plural = Some(Parser::parse(value)?),
+            _ => {}
+        }
+    }
+    Some((nplurals?, plural?))
+}
+
+/// The C expression of `plural`, selecting the index of the form for `n`.
+#[derive(Debug)]
+enum Expr {
+    N,
+    Number(u64),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Expr {
+    /// The value for `n` in unsigned arithmetic as `gettext`'s, `None` on division by zero.
+    fn eval(&self, n: u64) -> Option<u64> {
+        Some(match self {
+            Self::N => n,
+            Self::Number(number) => *number,
+            Self::Not(expr) => u64::from(expr.eval(n)? == 0),
+            Self::Conditional(condition, then, otherwise) => match condition.eval(n)? {
+                0 => otherwise.eval(n)?,
+                _ => then.eval(n)?,
+            },
+            Self::Binary(Op::Or, a, b) => u64::from(a.eval(n)? != 0 || b.eval(n)? != 0),
+            Self::Binary(Op::And, a, b) => u64::from(a.eval(n)? != 0 && b.eval(n)? != 0),
+            Self::Binary(op, a, b) => {
+                let (a, b) = (a.eval(n)?, b.eval(n)?);
+                match op {
+                    Op::Eq => u64::from(a == b),
+                    Op::Ne => u64::from(a != b),
+                    Op::Lt => u64::from(a < b),
+                    Op::Le => u64::from(a <= b),
+                    Op::Gt => u64::from(a > b),
+                    Op::Ge => u64::from(a >= b),
+                    Op::Add => a.wrapping_add(b),
+                    Op::Sub => a.wrapping_sub(b),
+                    Op::Mul => a.wrapping_mul(b),
+                    Op::Div => a.checked_div(b)?,
+                    Op::Rem => a.checked_rem(b)?,
+                    Op::Or | Op::And => {
+                        unreachable!("short-circuiting operators are matched above")
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Recursive descent parser of `plural` expressions, with C precedence.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Binary operators by increasing precedence.
+    const LEVELS: &'static [&'static [(&'static str, Op)]] = &[
+        &[("||", Op::Or)],
+        &[("&&", Op::And)],
+        &[("==", Op::Eq), ("!=", Op::Ne)],
+        &[("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)],
+        &[("+", Op::Add), ("-", Op::Sub)],
+        &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+    ];
+
+    fn parse(s: &'a str) -> Option<Expr> {
+        let mut parser = Parser { rest: s };
+        let expr = parser.conditional()?;
+        parser.rest.trim().is_empty().then_some(expr)
+    }
+
+    /// Consume `token` if the rest starts with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn conditional(&mut self) -> Option<Expr> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Some(condition);
+        }
+        let then = self.conditional()?;
+        self.eat(":").then_some(())?;
+        let otherwise = self.conditional()?;
+        Some(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Option<Expr> {
+        let Some(ops) = Self::LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut expr = self.binary(level + 1)?;
+        'operands: loop {
+            for (token, op) in *ops {
+                if self.eat(token) {
+                    let rhs = self.binary(level + 1)?;
+                    expr = Expr::Binary(*op, Box::new(expr), Box::new(rhs));
+                    continue 'operands;
+                }
+            }
+            return Some(expr);
+        }
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        if self.eat("!") {
+            return Some(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.conditional()?;
+            return self.eat(")").then_some(expr);
+        }
+        if self.eat("n") {
+            return Some(Expr::N);
+        }
+        let digits = self.rest.len()
+            - self
+                .rest
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let number = self.rest[..digits].parse().ok()?;
+        self.rest = &self.rest[digits..];
+        Some(Expr::Number(number))
+    }
+}
+
+/// The locale of `de/LC_MESSAGES/app.po` or `de.po`.
+fn locale_from_path(path: &Path) -> Option<String> {
+    let parent = path.parent()?;
+    if parent.file_name()? == "LC_MESSAGES" {
+        return Some(parent.parent()?.file_name()?.to_str()?.to_owned());
+    }
+    Some(path.file_stem()?.to_str()?.to_owned())
+}
+
+/// Parse the messages of a `.po` file, errors come with their 1-based line.
+fn parse_po(content: &str) -> Result<Vec<Message>, (usize, SyntaxError)> {
+    /// The string a line without keyword continues.
+    enum Field {
+        None,
+        Context,
+        Id,
+        Plural,
+        Str(usize),
+    }
+
+    let mut messages = Vec::new();
+    let mut message = Message::default();
+    // Whether `message` has a `msgid` or `msgstr`, so a new keyword starts the next one.
+    let (mut has_id, mut has_str) = (false, false);
+    let mut field = Field::None;
+
+    for (index, line) in content.lines().enumerate() {
+        let error = |message: &str| (index + 1, syntax_error(message));
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(flags) = line.strip_prefix("#,") {
+            // Flags precede the message they apply to.
+            if has_str {
+                messages.push(std::mem::take(&mut message));
+                (has_id, has_str, field) = (false, false, Field::None);
+            }
+            message.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            continue;
+        }
+        if line.starts_with('#') {
+            // Comments and obsolete messages.
+            continue;
+        }
+
+        let (keyword, rest) = match line.find(|c: char| c.is_whitespace()) {
+            Some(_) if line.starts_with('"') => ("", line),
+            Some(at) => (&line[..at], line[at..].trim_start()),
+            None if line.starts_with('"') => ("", line),
+            None => return Err(error("expected a keyword followed by a string")),
+        };
+        let string = unquote(rest).map_err(|e| (index + 1, e))?;
+
+        if !keyword.is_empty() && has_str && !keyword.starts_with("msgstr") {
+            messages.push(std::mem::take(&mut message));
+            (has_id, has_str) = (false, false);
+        }
+        field = match keyword {
+            "" => {
+                match field {
+                    Field::None => return Err(error("string without keyword")),
+                    Field::Context => message.context.get_or_insert_with(String::new),
+                    Field::Id => &mut message.id,
+                    Field::Plural => message.plural.get_or_insert_with(String::new),
+                    Field::Str(n) => &mut message.strs[n],
+                }
+                .push_str(&string);
+                continue;
+            }
+            "msgctxt" if !has_id => {
+                message.context = Some(string);
+                Field::Context
+            }
+            "msgid" if !has_id => {
+                message.id = string;
+                has_id = true;
+                Field::Id
+            }
+            "msgid_plural" if has_id && !has_str => {
+                message.plural = Some(string);
+                Field::Plural
+            }
+            "msgstr" if has_id && message.plural.is_none() && !has_str => {
+                message.strs.push(string);
+                has_str = true;
+                Field::Str(0)
+            }
+            keyword if has_id && message.plural.is_some() && keyword.starts_with("msgstr[") => {
+                let n = keyword
+                    .strip_prefix("msgstr[")
+                    .and_then(|n| n.strip_suffix(']'))
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|n| *n == message.strs.len())
+                    .ok_or_else(|| error("expected the next plural form index"))?;
+                message.strs.push(string);
+                has_str = true;
+                Field::Str(n)
+            }
+            "msgctxt" | "msgid" | "msgid_plural" | "msgstr" => {
+                return Err(error(&format!("unexpected `{keyword}`")))
+            }
+            _ => return Err(error(&format!("unknown keyword `{keyword}`"))),
+        };
+    }
+    if has_id {
+        if !has_str {
+            return Err((content.lines().count(), syntax_error("missing `msgstr`")));
+        }
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// The content of a quoted C string.
+fn unquote(s: &str) -> Result<String, SyntaxError> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .filter(|_| s.len() >= 2)
+        .ok_or_else(|| syntax_error("expected a quoted string"))?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('a') => '\x07',
+                Some('b') => '\x08',
+                Some('f') => '\x0c',
+                Some('v') => '\x0b',
+                Some(c @ ('\\' | '"' | '\'' | '?')) => c,
+                Some(c) => return Err(syntax_error(format!("unknown escape `\\{c}`"))),
+                None => return Err(syntax_error("unterminated escape")),
+            }),
+            '"' => return Err(syntax_error("unescaped `\"` in string")),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Parse the messages of a `.mo` file.
+fn parse_mo(bytes: &[u8]) -> Result<Vec<Message>, SyntaxError> {
+    const MAGIC: u32 = 0x9504_12de;
+
+    let word = |offset: usize, big_endian: bool| -> Result<usize, SyntaxError> {
+        let word: [u8; 4] = bytes
+            .get(offset..offset + 4)
+            .and_then(|word| word.try_into().ok())
+            .ok_or_else(|| syntax_error("truncated file"))?;
+        let word = if big_endian {
+            u32::from_be_bytes(word)
+        } else {
+            u32::from_le_bytes(word)
+        };
+        Ok(word as usize)
+    };
+    let big_endian = match word(0, false)? as u32 {
+        MAGIC => false,
+        magic if magic.swap_bytes() == MAGIC => true,
+        _ => return Err(syntax_error("not a gettext .mo file")),
+    };
+    let word = |offset| word(offset, big_endian);
+    if word(4)? >> 16 > 1 {
+        return Err(syntax_error("unsupported .mo file revision"));
+    }
+    let count = word(8)?;
+    let (originals, translations) = (word(12)?, word(16)?);
+    let string = |table: usize, index: usize| -> Result<&str, SyntaxError> {
+        let entry = table + index * 8;
+        let (len, offset) = (word(entry)?, word(entry + 4)?);
+        let bytes = bytes
+            .get(offset..offset + len)
+            .ok_or_else(|| syntax_error("string out of bounds"))?;
+        std::str::from_utf8(bytes).map_err(|e| syntax_error(e.to_string()))
+    };
+
+    (0..count)
+        .map(|index| {
+            let original = string(originals, index)?;
+            let (context, original) = match original.split_once('\x04') {
+                Some((context, original)) => (Some(context.to_owned()), original),
+                None => (None, original),
+            };
+            let (id, plural) = match original.split_once('\0') {
+                Some((id, plural)) => (id, Some(plural.to_owned())),
+                None => (original, None),
+            };
+            Ok(Message {
+                context,
+                id: id.to_owned(),
+                plural,
+                strs: string(translations, index)?
+                    .split('\0')
+                    .map(str::to_owned)
+                    .collect(),
+                fuzzy: false,
+            })
+        })
+        .collect()
+}
@@ -1,13 +1,24 @@
-use fs::File;
 use fs_err as fs;
 use glob::glob;
-use std::collections::HashMap;
-use std::io::prelude::*;
-use std::io::Write;
-
-pub type Locale = String;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub mod blob;
+pub mod catalog;
+pub mod config;
+pub mod conflict;
+pub mod format;
+pub mod gettext;
+pub mod locale;
+pub mod plural;
+mod reference;
+pub mod template;
+
+pub use locale::{InvalidLocale, Locale};
 pub type Value = serde_json::Value;
-pub type Translations = HashMap<Locale, Value>;
+/// Translations per locale, as read from a locale file.
+pub type Translations = BTreeMap<String, Value>;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -25,22 +36,95 @@ pub enum Error {
 
     #[error(transparent)]
     Yaml(#[from] serde_yaml::Error),
+
+    #[error("Failed to list locale files in {}: {source}", .path.display())]
+    Glob {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to read {}: {source}", .path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse {}{}: {source}", .path.display(), location(*.line, *.column))]
+    Parse {
+        path: PathBuf,
+        /// 1-based line of the error, if known.
+        line: Option<usize>,
+        /// 1-based column of the error, if known.
+        column: Option<usize>,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("{source} in {}", .path.display())]
+    InvalidLocale {
+        path: PathBuf,
+        source: InvalidLocale,
+    },
+
+    #[error("Invalid locale `{locale}` in {}: expected a mapping of keys to translations", .path.display())]
+    NotAMapping { path: PathBuf, locale: String },
+
+    #[error("No locale in the path of {}: expected a locale directory, `de/views.yml`, or suffix, `views.de.yml`", .path.display())]
+    NoLocale { path: PathBuf },
+
+    #[error("`{key}` in {} references `{target}`, which has no translation in its locale", .path.display())]
+    DanglingReference {
+        path: PathBuf,
+        key: String,
+        target: String,
+    },
+
+    #[error("References in {} form a cycle: {}", .path.display(), .cycle.join(" -> "))]
+    ReferenceCycle { path: PathBuf, cycle: Vec<String> },
+
+    #[error("Conflicting translations: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Conflicts(Vec<Conflict>),
+
+    #[error("OUT_DIR is not set, translations must be prepared from a build script")]
+    OutDirMissing,
+
+    #[error("Not a rust-i18n catalog file")]
+    NotACatalog,
+
+    #[error("Catalog file format version {found} is not supported, expected {expected}; rebuild with the same rust-i18n-support version as rust-i18n")]
+    FormatVersion { found: u32, expected: u32 },
+
+    #[error("Catalog file is corrupt, its header lists locales {header:?} but it has translations for {found:?}")]
+    LocalesMismatch {
+        header: Vec<String>,
+        found: Vec<String>,
+    },
+
+    #[error("Catalog file is stale, the locale files in {} changed since it was built", .locales_dir.display())]
+    Stale { locales_dir: PathBuf },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-/// Init I18n translations from `build.rs`.
-///
-/// This will load all translations by glob `**/*.yml` from the
-/// given path and prepare a file to be included in the compiled proc macro.
-pub fn load_from_dirs(locale_dir: impl AsRef<std::path::Path>) -> Result<()> {
-    let locale_path = locale_dir.as_ref();
-    let translations = locales_yaml_files_to_translation_map(locale_path)?;
-    let translations = serialize(translations)?;
+/// The `:line:column` suffix of a path, as far as known.
+fn location(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!(":{line}:{column}"),
+        (Some(line), None) => format!(":{line}"),
+        _ => String::new(),
+    }
+}
 
-    fs::write("foo-bar-baz", translations)?;
+pub use blob::{deserialize, deserialize_fresh, serialize};
+pub use catalog::Catalog;
+use config::{I18nConfig, Layout, Severity};
+pub use conflict::Conflict;
+use conflict::Origins;
 
-    Ok(())
+/// Init I18n translations from `build.rs`.
+///
+/// Same as [`prepare`].
+pub fn load_from_dirs(locale_dir: impl AsRef<Path>) -> Result<()> {
+    prepare(locale_dir)
 }
 
 /// Path to an translation item
@@ -50,17 +134,10 @@ pub type TranslationPath = String;
 
 /// Optimize for proc-macro parsing ease,
 /// that's called 1 vs n times more often!
-pub type TranslationMap = HashMap<TranslationPath, HashMap<Locale, String>>;
-
-pub fn deserialize(bytes: &[u8]) -> Result<TranslationMap> {
-    let tmap: TranslationMap = postcard::from_bytes(bytes)?;
-    Ok(tmap)
-}
-
-pub fn serialize(text2translations: TranslationMap) -> Result<Vec<u8>> {
-    let bytes = postcard::to_allocvec(&text2translations)?;
-    Ok(bytes)
-}
+///
+/// Ordered, so the serialized catalog and generated code are reproducible.
+/// Locales are canonical, see [`Locale`].
+pub type TranslationMap = BTreeMap<TranslationPath, BTreeMap<String, String>>;
 
 /// Merge JSON Values, merge b into a
 ///
@@ -68,7 +145,7 @@ pub fn serialize(text2translations: TranslationMap) -> Result<Vec<u8>> {
 /// and recurses into all objects.
 pub fn merge_value(a: &mut Value, b: &Value) {
     match (a, b) {
-        (&mut Value::Object(ref mut a), &Value::Object(ref b)) => {
+        (Value::Object(a), Value::Object(b)) => {
             for (k, v) in b {
                 merge_value(a.entry(k.clone()).or_insert(Value::Null), v);
             }
@@ -79,16 +156,101 @@ pub fn merge_value(a: &mut Value, b: &Value) {
     }
 }
 
-fn extract_yaml_content(
-    yaml_content: impl AsRef<str>,
-    trans_map: &mut HashMap<TranslationPath, serde_json::Value>,
-) -> Result<()> {
-    // All translation items per language
-    let trs: Translations = serde_yaml::from_str(yaml_content.as_ref())?;
+/// Parse the locale file at `path` in `locales_dir` into translations per locale.
+fn parse_file(
+    locales_dir: &Path,
+    path: &Path,
+    content: &[u8],
+    config: &I18nConfig,
+) -> Result<Translations> {
+    let format = format::for_path(path).unwrap_or(&format::Yaml);
+    let trs = format.parse(path, content)?;
+    if config.layout == Layout::LocaleKey || format.names_locale() {
+        return canonical_locales(path, trs);
+    }
 
-    eprintln!("cargo:warning: foo: -- {:?}", &trs);
+    let (locale, namespace) = path_locale(locales_dir, path)?;
+    let namespace = if config.namespaces {
+        namespace
+    } else {
+        Vec::new()
+    };
+    let value = namespace
+        .into_iter()
+        .rev()
+        .fold(Value::Object(trs.into_iter().collect()), |value, ns| {
+            Value::Object([(ns, value)].into_iter().collect())
+        });
+    canonical_locales(path, Translations::from([(locale, value)]))
+}
+
+/// Canonicalise the locales of `trs`, merging the translations of equivalent ones.
+fn canonical_locales(path: &Path, trs: Translations) -> Result<Translations> {
+    let mut canonical = Translations::new();
+    for (locale, value) in trs {
+        let locale = Locale::parse(&locale).map_err(|source| Error::InvalidLocale {
+            path: path.to_owned(),
+            source,
+        })?;
+        match canonical.entry(locale.into()) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            Entry::Occupied(mut entry) => merge_value(entry.get_mut(), &value),
+        }
+    }
+    Ok(canonical)
+}
+
+/// The locale and namespace of the file at `path` with [`Layout::Path`].
+///
+/// `de/views.yml` and `views.de.yml` are in `de` with namespace `views`.
+/// Subdirectories are part of the namespace, `admin.users` for
+/// `de/admin/users.yml`. A file directly in `locales_dir` without a locale
+/// suffix, like `views.yml`, is an error.
+fn path_locale(locales_dir: &Path, path: &Path) -> Result<(String, Vec<String>)> {
+    let relative = path.strip_prefix(locales_dir).unwrap_or(path);
+    let mut dirs: Vec<String> = relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let stem = relative
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    if let Some((name, locale)) = stem.rsplit_once('.') {
+        dirs.push(name.to_owned());
+        Ok((locale.to_owned(), dirs))
+    } else if !dirs.is_empty() {
+        let locale = dirs.remove(0);
+        dirs.push(stem);
+        Ok((locale, dirs))
+    } else {
+        Err(Error::NoLocale {
+            path: path.to_owned(),
+        })
+    }
+}
+
+/// Merge the translations `trs` of the file at `path` into `trans_map`.
+fn extract_content(
+    path: &Path,
+    trs: Translations,
+    trans_map: &mut BTreeMap<TranslationPath, serde_json::Value>,
+    origins: &mut Origins,
+) -> Result<()> {
+    if let Some((locale, _)) = trs.iter().find(|(_, v)| !v.is_object()) {
+        return Err(Error::NotAMapping {
+            path: path.to_owned(),
+            locale: locale.clone(),
+        });
+    }
 
     trs.into_iter().for_each(|(tp, translations)| {
+        origins.visit(path, &tp, &translations);
         trans_map
             .entry(tp)
             .and_modify(|translations_old| merge_value(translations_old, &translations))
@@ -97,7 +259,7 @@ fn extract_yaml_content(
     Ok(())
 }
 
-fn trans_map_voodoo(trans_map: HashMap<TranslationPath, serde_json::Value>) -> TranslationMap {
+fn trans_map_voodoo(trans_map: BTreeMap<TranslationPath, serde_json::Value>) -> TranslationMap {
     let mut tp2trans_per_locale = TranslationMap::new();
 
     // let mut locale_vars = HashMap::<String, String>::new();
@@ -124,43 +286,66 @@ fn trans_map_voodoo(trans_map: HashMap<TranslationPath, serde_json::Value>) -> T
     tp2trans_per_locale
 }
 
-// Load locales into flatten key,value HashMap
-pub fn locales_yaml_files_to_translation_map(
-    locales_dir: &std::path::Path,
-) -> Result<TranslationMap> {
-    let mut trans_map = Translations::new();
-
-    let path_pattern = format!("{}/**/*.yml", locales_dir.display());
-
-    println!("cargo:i18n-locale={}", &path_pattern);
+fn locale_files_pattern(locales_dir: &Path) -> String {
+    let locales_dir = glob::Pattern::escape(&locales_dir.display().to_string());
+    format!("{}/**/*", locales_dir)
+}
 
-    let paths = glob(&path_pattern).expect("Failed to read glob pattern");
-    for maybe_path in paths {
-        let path = if let Ok(path) = maybe_path {
-            path
-        } else {
-            continue;
-        };
-        println!("cargo:i18n-load={}", &path.display());
+/// All locale files in `locales_dir` and its subdirectories,
+/// in any of the supported [`format::FORMATS`].
+pub fn locale_files(locales_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for maybe_path in
+        glob(&locale_files_pattern(locales_dir)).expect("Escaped glob pattern is valid")
+    {
+        let path = maybe_path.map_err(|e| Error::Glob {
+            path: e.path().to_owned(),
+            source: e.into(),
+        })?;
+        if path.is_file() && format::for_path(&path).is_some() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
 
-        let file = File::open(path).expect("Failed to open the YAML file");
-        let mut reader = std::io::BufReader::new(file);
-        let mut content = String::new();
+// Load locales into flatten key,value BTreeMap
+pub fn locales_yaml_files_to_translation_map(locales_dir: &Path) -> Result<TranslationMap> {
+    load_locales(locales_dir, &I18nConfig::default())
+        .map(|(tp2trans_per_locale, _)| tp2trans_per_locale)
+}
 
-        reader.read_to_string(&mut content)?;
-        extract_yaml_content(content, &mut trans_map)?;
+/// Load all locale files in `locales_dir`, like [`locales_yaml_files_to_translation_map`],
+/// in the [`Layout`] of `config`.
+///
+/// Also returns the keys that several files define differently.
+pub fn load_locales(
+    locales_dir: &Path,
+    config: &I18nConfig,
+) -> Result<(TranslationMap, Vec<Conflict>)> {
+    let mut trans_map = Translations::new();
+    let mut origins = Origins::default();
+
+    for path in locale_files(locales_dir)? {
+        let content = std::fs::read(&path).map_err(|source| Error::Read {
+            path: path.clone(),
+            source,
+        })?;
+        let trs = parse_file(locales_dir, &path, &content, config)?;
+        extract_content(&path, trs, &mut trans_map, &mut origins)?;
     }
 
-    let tp2trans_per_locale = trans_map_voodoo(trans_map);
+    let mut tp2trans_per_locale = trans_map_voodoo(trans_map);
+    reference::resolve(&mut tp2trans_per_locale, &origins)?;
 
-    Ok(tp2trans_per_locale)
+    Ok((tp2trans_per_locale, origins.conflicts))
 }
 
 /// Find the value based on it's path aka prefix `a.b.c`
 ///
 /// Returns a `prefix`:`value` set.
-pub fn extract_vars(prefix: &str, trs: &Value) -> HashMap<String, String> {
-    let mut v = HashMap::<String, String>::new();
+pub fn extract_vars(prefix: &str, trs: &Value) -> BTreeMap<String, String> {
+    let mut v = BTreeMap::<String, String>::new();
     let prefix = prefix.to_string();
 
     match &trs {
@@ -182,31 +367,92 @@ pub fn extract_vars(prefix: &str, trs: &Value) -> HashMap<String, String> {
         serde_json::Value::Number(s) => {
             v.insert(prefix, format!("{}", s));
         }
-        serde_json::Value::Array(_) => {
-            v.insert(prefix, "".into());
+        serde_json::Value::Array(items) => {
+            // Items are keyed by index, `list.0`, see `Catalog::list`.
+            for (idx, item) in items.iter().enumerate() {
+                let key = format!("{}.{}", prefix, idx);
+                v.extend(extract_vars(key.as_str(), item));
+            }
         }
     }
 
     v
 }
 
-/// Init I18n translations.
+/// Name of the compiled translations in `OUT_DIR`.
+pub const CATALOG_FILE_NAME: &str = "rust_i18n_catalog.bin";
+
+/// Environment variable passing the path of the compiled translations
+/// from the build script to `format_t!`.
+pub const CATALOG_ENV: &str = "RUST_I18N_CATALOG";
+
+/// Init I18n translations from `build.rs`.
 ///
-/// This will load all translations by glob `**/*.yml` from the given path and prepare a file to be included in the compiled proc macro.
-pub fn prepare(locale_dir: impl AsRef<std::path::Path>) -> Result<()> {
+/// This will load all translations by glob `**/*.{yml,yaml,json,toml,po,mo}` from the given path and write them to `OUT_DIR`,
+/// to be read by `format_t!` instead of the YAML files. Cargo reruns the build script when a locale file changes.
+///
+/// Errors are also reported as cargo warnings, which are easier to read than the build script's output.
+pub fn prepare(locale_dir: impl AsRef<Path>) -> Result<()> {
     let locales_dir = locale_dir.as_ref();
+    let result = prepare_inner(locales_dir);
+    if let Err(err) = &result {
+        println!("cargo:warning={}", err.to_string().replace('\n', " "));
+        // Rerun once the broken files are fixed.
+        println!("cargo:rerun-if-changed={}", locales_dir.display());
+        if let Ok(files) = locale_files(locales_dir) {
+            for path in files {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+    result
+}
 
-    let translations = locales_yaml_files_to_translation_map(&locales_dir)?;
-
-    let serialized = self::serialize(translations)?;
-    let mut f = fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(locales_dir.join("foo-bar-baz"))?;
-    f.write_all(serialized.as_slice())?;
+fn prepare_inner(locales_dir: &Path) -> Result<()> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(Error::OutDirMissing)?;
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR");
+    let config = match &manifest_dir {
+        Some(manifest_dir) => config::load(Path::new(manifest_dir))?,
+        None => I18nConfig::default(),
+    };
+
+    let path = write_catalog(locales_dir, Path::new(&out_dir), &config)?;
+
+    println!("cargo:rustc-env={}={}", CATALOG_ENV, path.display());
+    // The layout of the locale files is configured there.
+    if let Some(manifest_dir) = manifest_dir {
+        let manifest = Path::new(&manifest_dir).join("Cargo.toml");
+        println!("cargo:rerun-if-changed={}", manifest.display());
+    }
+    println!("cargo:rerun-if-changed={}", locales_dir.display());
+    for path in locale_files(locales_dir)? {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
     Ok(())
 }
 
+/// Compile the translations in `locales_dir` to [`CATALOG_FILE_NAME`] in `out_dir`.
+///
+/// Conflicting keys are reported as cargo warnings or errors, according to `config.conflicts`.
+/// Returns the path of the written file.
+pub fn write_catalog(locales_dir: &Path, out_dir: &Path, config: &I18nConfig) -> Result<PathBuf> {
+    let (translations, found) = load_locales(locales_dir, config)?;
+    match config.conflicts {
+        Severity::Deny if !found.is_empty() => return Err(Error::Conflicts(found)),
+        Severity::Warn => {
+            for conflict in found {
+                println!("cargo:warning={}", conflict);
+            }
+        }
+        _ => {}
+    }
+    let content_hash = blob::content_hash(locales_dir, &locale_files(locales_dir)?)?;
+
+    let serialized = self::serialize(translations, content_hash)?;
+    let path = out_dir.join(CATALOG_FILE_NAME);
+    fs::write(&path, serialized)?;
+    Ok(path)
+}
+
 #[cfg(test)]
 mod tests;
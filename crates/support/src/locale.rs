@@ -0,0 +1,209 @@
+//! BCP 47 language tags, see <https://www.rfc-editor.org/rfc/rfc5646>.
+//!
+//! Tags are compared in their canonical form, so `zh_CN`, `zh-cn` and `zh-CN`
+//! are the same locale: subtags are separated by `-`, the script is title
+//! case, the region upper case and everything else lower case.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::{Borrow, Cow};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// A valid BCP 47 language tag, in canonical form.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Locale(String);
+
+/// A string that is not a BCP 47 language tag.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid locale `{tag}`: {reason}")]
+pub struct InvalidLocale {
+    pub tag: String,
+    pub reason: &'static str,
+}
+
+impl Locale {
+    /// Parse and canonicalise a language tag, `_` is accepted as separator.
+    pub fn parse(tag: &str) -> Result<Self, InvalidLocale> {
+        let subtags = tag.split(['-', '_']).collect::<Vec<_>>();
+        check(&subtags).map_err(|reason| InvalidLocale {
+            tag: tag.to_owned(),
+            reason,
+        })?;
+        Ok(Self(canonical(&subtags)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// `tag` in canonical form, or as is if it is not a valid language tag.
+///
+/// Only allocates if `tag` is valid, but not canonical.
+pub fn canonicalize(tag: &str) -> Cow<'_, str> {
+    let subtags = tag.split(['-', '_']).collect::<Vec<_>>();
+    if check(&subtags).is_err() {
+        return Cow::Borrowed(tag);
+    }
+    let canonical = canonical(&subtags);
+    if canonical == tag {
+        Cow::Borrowed(tag)
+    } else {
+        Cow::Owned(canonical)
+    }
+}
+
+fn is_alpha(s: &str, len: impl std::ops::RangeBounds<usize>) -> bool {
+    len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+fn is_alphanumeric(s: &str, len: impl std::ops::RangeBounds<usize>) -> bool {
+    len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn is_region(s: &str) -> bool {
+    is_alpha(s, 2..=2) || (s.len() == 3 && s.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn is_variant(s: &str) -> bool {
+    is_alphanumeric(s, 5..=8) || (is_alphanumeric(s, 4..=4) && s.as_bytes()[0].is_ascii_digit())
+}
+
+/// Check the grammar of a `langtag` or `privateuse` tag.
+fn check(subtags: &[&str]) -> Result<(), &'static str> {
+    /// Skip the next subtag if it is accepted.
+    fn next(rest: &mut &[&str], accept: impl Fn(&str) -> bool) -> bool {
+        match rest.first() {
+            Some(subtag) if accept(subtag) => {
+                *rest = &rest[1..];
+                true
+            }
+            _ => false,
+        }
+    }
+
+    let mut rest = subtags;
+
+    let private = |rest: &[&str]| {
+        if !rest.is_empty() && rest.iter().all(|s| is_alphanumeric(s, 1..=8)) {
+            Ok(())
+        } else {
+            Err("expected private use subtags of 1 to 8 letters or digits after `x`")
+        }
+    };
+    if next(&mut rest, |s| s.eq_ignore_ascii_case("x")) {
+        return private(rest);
+    }
+
+    if !next(&mut rest, |s| is_alpha(s, 2..=8)) {
+        return Err("expected a language of 2 to 8 letters");
+    }
+    if subtags[0].len() <= 3 {
+        for _ in 0..3 {
+            if !next(&mut rest, |s| is_alpha(s, 3..=3)) {
+                break;
+            }
+        }
+    }
+    next(&mut rest, |s| is_alpha(s, 4..=4));
+    next(&mut rest, is_region);
+    while next(&mut rest, is_variant) {}
+    while let Some(singleton) = rest.first().filter(|s| is_alphanumeric(s, 1..=1)) {
+        if singleton.eq_ignore_ascii_case("x") {
+            return private(&rest[1..]);
+        }
+        rest = &rest[1..];
+        if !next(&mut rest, |s| is_alphanumeric(s, 2..=8)) {
+            return Err("expected an extension subtag of 2 to 8 letters or digits");
+        }
+        while next(&mut rest, |s| is_alphanumeric(s, 2..=8)) {}
+    }
+    match rest.first() {
+        None => Ok(()),
+        Some(_) => Err("unexpected subtag"),
+    }
+}
+
+/// Join valid `subtags` in canonical case.
+fn canonical(subtags: &[&str]) -> String {
+    let mut tag = String::new();
+    // Script and region casing only applies before extensions.
+    let mut in_extension = false;
+    for (idx, subtag) in subtags.iter().enumerate() {
+        if idx > 0 {
+            tag.push('-');
+        }
+        in_extension |= subtag.len() == 1;
+        if !in_extension && idx > 0 && subtag.len() == 4 && is_alpha(subtag, 4..=4) {
+            tag.push_str(&subtag[..1].to_ascii_uppercase());
+            tag.push_str(&subtag[1..].to_ascii_lowercase());
+        } else if !in_extension && idx > 0 && subtag.len() == 2 {
+            tag.push_str(&subtag.to_ascii_uppercase());
+        } else {
+            tag.push_str(&subtag.to_ascii_lowercase());
+        }
+    }
+    tag
+}
+
+impl FromStr for Locale {
+    type Err = InvalidLocale;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = InvalidLocale;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<Locale> for String {
+    fn from(locale: Locale) -> Self {
+        locale.0
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for Locale {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Locale {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Locale {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Locale {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
@@ -0,0 +1,398 @@
+//! CLDR cardinal plural rules.
+//!
+//! Selects which of the `zero`, `one`, `two`, `few`, `many` and `other`
+//! translations of a key to use for a count, see
+//! <https://unicode-org.github.io/cldr-staging/charts/latest/supplemental/language_plural_rules.html>.
+
+use std::fmt;
+
+/// A CLDR plural category, as used as last segment of a translation key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub const ALL: [PluralCategory; 6] = [
+        Self::Zero,
+        Self::One,
+        Self::Two,
+        Self::Few,
+        Self::Many,
+        Self::Other,
+    ];
+
+    /// The key segment of the category, i.e. `one`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zero => "zero",
+            Self::One => "one",
+            Self::Two => "two",
+            Self::Few => "few",
+            Self::Many => "many",
+            Self::Other => "other",
+        }
+    }
+
+    /// Parse a key segment.
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == key)
+    }
+}
+
+impl fmt::Display for PluralCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The CLDR plural operands of a number.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PluralOperands {
+    /// Absolute value.
+    pub n: f64,
+    /// Integer digits.
+    pub i: u64,
+    /// Number of visible fraction digits, with trailing zeros.
+    pub v: usize,
+    /// Number of visible fraction digits, without trailing zeros.
+    pub w: usize,
+    /// Visible fraction digits, with trailing zeros.
+    pub f: u64,
+    /// Visible fraction digits, without trailing zeros.
+    pub t: u64,
+}
+
+impl PluralOperands {
+    /// Operands of a decimal number as written, `"1.50"` has two visible fraction digits.
+    ///
+    /// Returns `None` if `s` is not a decimal number.
+    pub fn from_decimal(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix('-').unwrap_or(s);
+        let (int, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !all_digits(int) || !all_digits(fraction) {
+            return None;
+        }
+        let trimmed = fraction.trim_end_matches('0');
+        let digits = |s: &str| {
+            if s.is_empty() {
+                Some(0)
+            } else {
+                s.parse().ok()
+            }
+        };
+        Some(Self {
+            n: s.parse().ok()?,
+            i: int.parse().ok()?,
+            v: fraction.len(),
+            w: trimmed.len(),
+            f: digits(fraction)?,
+            t: digits(trimmed)?,
+        })
+    }
+
+    fn from_integer(i: u128) -> Option<Self> {
+        Some(Self {
+            n: i as f64,
+            i: i.try_into().ok()?,
+            ..Self::default()
+        })
+    }
+}
+
+/// A count to select a plural category for.
+pub trait PluralOperand {
+    /// The operands of the count, `None` if it has none the rules can handle,
+    /// like NaN or integers beyond `u64`.
+    fn operands(&self) -> Option<PluralOperands>;
+}
+
+macro_rules! impl_plural_operand_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl PluralOperand for $ty {
+                fn operands(&self) -> Option<PluralOperands> {
+                    PluralOperands::from_integer(*self as u128)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_plural_operand_signed {
+    ($($ty:ty),*) => {
+        $(
+            impl PluralOperand for $ty {
+                fn operands(&self) -> Option<PluralOperands> {
+                    PluralOperands::from_integer(self.unsigned_abs() as u128)
+                }
+            }
+        )*
+    };
+}
+
+impl_plural_operand_unsigned!(u8, u16, u32, u64, u128, usize);
+impl_plural_operand_signed!(i8, i16, i32, i64, i128, isize);
+
+impl PluralOperand for f32 {
+    fn operands(&self) -> Option<PluralOperands> {
+        self.to_string().as_str().operands()
+    }
+}
+
+impl PluralOperand for f64 {
+    fn operands(&self) -> Option<PluralOperands> {
+        self.to_string().as_str().operands()
+    }
+}
+
+/// Strings are parsed as decimal numbers.
+impl PluralOperand for str {
+    fn operands(&self) -> Option<PluralOperands> {
+        PluralOperands::from_decimal(self)
+    }
+}
+
+impl PluralOperand for String {
+    fn operands(&self) -> Option<PluralOperands> {
+        self.as_str().operands()
+    }
+}
+
+impl<T: PluralOperand + ?Sized> PluralOperand for &T {
+    fn operands(&self) -> Option<PluralOperands> {
+        (**self).operands()
+    }
+}
+
+/// The language subtag of a locale, `pt` for `pt-BR`.
+fn language(locale: &str) -> &str {
+    locale.split(['-', '_']).next().unwrap_or(locale)
+}
+
+/// Whether `locale` has the region subtag `region`, like `PT` of `pt-PT`.
+fn has_region(locale: &str, region: &str) -> bool {
+    locale
+        .split(['-', '_'])
+        .skip(1)
+        .any(|subtag| subtag.eq_ignore_ascii_case(region))
+}
+
+/// Whether `x` is an integer in `range`, as the CLDR `n = 3..6`.
+fn in_range(x: f64, range: std::ops::RangeInclusive<u64>) -> bool {
+    x.fract() == 0.0 && range.contains(&(x as u64))
+}
+
+/// The plural categories used by the language of `locale`.
+///
+/// Languages without known rules only use `other`.
+pub fn categories(locale: &str) -> &'static [PluralCategory] {
+    use PluralCategory::*;
+    match rule(locale) {
+        Rule::Other => &[Other],
+        Rule::OneInteger
+        | Rule::One
+        | Rule::OneZeroOrOne
+        | Rule::OneBelowTwo
+        | Rule::Danish
+        | Rule::Macedonian
+        | Rule::Icelandic => &[One, Other],
+        Rule::French | Rule::Spanish | Rule::Italian => &[One, Many, Other],
+        Rule::Slavic | Rule::Polish | Rule::Czech | Rule::Lithuanian => &[One, Few, Many, Other],
+        Rule::SerboCroatian | Rule::Romanian => &[One, Few, Other],
+        Rule::Latvian => &[Zero, One, Other],
+        Rule::Slovenian => &[One, Two, Few, Other],
+        Rule::Hebrew => &[One, Two, Many, Other],
+        Rule::Irish => &[One, Two, Few, Many, Other],
+        Rule::Arabic | Rule::Welsh => &[Zero, One, Two, Few, Many, Other],
+    }
+}
+
+/// The plural categories integers of the language of `locale` fall into,
+/// in the order of [`categories`].
+///
+/// Leaves out the categories only used for decimals, `other` in Russian.
+pub fn integer_categories(locale: &str) -> &'static [PluralCategory] {
+    use PluralCategory::*;
+    match rule(locale) {
+        Rule::Slavic | Rule::Polish => &[One, Few, Many],
+        Rule::Czech | Rule::Lithuanian => &[One, Few, Other],
+        _ => categories(locale),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Rule {
+    /// Only `other`
+    Other,
+    /// `one`: i = 1 and v = 0
+    OneInteger,
+    /// `one`: n = 1
+    One,
+    /// `one`: i = 0,1
+    OneZeroOrOne,
+    /// `one`: i = 0 or n = 1
+    OneBelowTwo,
+    Danish,
+    /// `one`: i = 0,1, `many`: millions
+    French,
+    /// `one`: n = 1, `many`: millions
+    Spanish,
+    /// `one`: i = 1 and v = 0, `many`: millions
+    Italian,
+    Slavic,
+    Polish,
+    Czech,
+    SerboCroatian,
+    Romanian,
+    Hebrew,
+    Arabic,
+    Lithuanian,
+    Latvian,
+    Slovenian,
+    Irish,
+    Welsh,
+    Macedonian,
+    Icelandic,
+}
+
+fn rule(locale: &str) -> Rule {
+    match language(locale) {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "lo" | "my" | "km" | "yue" => Rule::Other,
+        "en" | "de" | "nl" | "sv" | "nb" | "nn" | "no" | "fi" | "et" | "gl" | "eo" | "ur"
+        | "sw" => Rule::OneInteger,
+        "fr" => Rule::French,
+        "pt" if has_region(locale, "PT") => Rule::Italian,
+        "pt" => Rule::French,
+        "es" => Rule::Spanish,
+        "it" | "ca" => Rule::Italian,
+        "el" | "hu" | "tr" | "bg" | "az" | "ka" | "kk" | "ky" | "mn" | "sq" | "ta" | "te"
+        | "ml" | "mr" | "ne" | "uz" | "af" | "eu" => Rule::One,
+        "hy" | "kab" => Rule::OneZeroOrOne,
+        "hi" | "bn" | "fa" | "gu" | "kn" | "zu" | "am" | "as" => Rule::OneBelowTwo,
+        "da" => Rule::Danish,
+        "ru" | "uk" | "be" => Rule::Slavic,
+        "pl" => Rule::Polish,
+        "cs" | "sk" => Rule::Czech,
+        "hr" | "sr" | "bs" | "sh" => Rule::SerboCroatian,
+        "ro" | "mo" => Rule::Romanian,
+        "he" | "iw" => Rule::Hebrew,
+        "ar" => Rule::Arabic,
+        "lt" => Rule::Lithuanian,
+        "lv" => Rule::Latvian,
+        "sl" => Rule::Slovenian,
+        "ga" => Rule::Irish,
+        "cy" => Rule::Welsh,
+        "mk" => Rule::Macedonian,
+        "is" => Rule::Icelandic,
+        _ => Rule::Other,
+    }
+}
+
+/// The plural category of `operands` in the language of `locale`.
+pub fn category(locale: &str, operands: &PluralOperands) -> PluralCategory {
+    use PluralCategory::*;
+    let PluralOperands { n, i, v, f, t, .. } = *operands;
+    let integer = v == 0;
+    let (i10, i100) = (i % 10, i % 100);
+    let (f10, f100) = (f % 10, f % 100);
+    let (n10, n100) = (n % 10.0, n % 100.0);
+    let millions = integer && i != 0 && i % 1_000_000 == 0;
+    match rule(locale) {
+        Rule::Other => Other,
+        Rule::OneInteger if i == 1 && integer => One,
+        Rule::One if n == 1.0 => One,
+        Rule::OneZeroOrOne if i <= 1 => One,
+        Rule::OneBelowTwo if i == 0 || n == 1.0 => One,
+        Rule::Danish if n == 1.0 || (t != 0 && i <= 1) => One,
+        Rule::French if i <= 1 => One,
+        Rule::Spanish if n == 1.0 => One,
+        Rule::Italian if i == 1 && integer => One,
+        Rule::French | Rule::Spanish | Rule::Italian if millions => Many,
+        Rule::Slavic if integer && i10 == 1 && i100 != 11 => One,
+        Rule::Slavic if integer && (2..=4).contains(&i10) && !(12..=14).contains(&i100) => Few,
+        Rule::Slavic if integer => Many,
+        Rule::Polish if integer && i == 1 => One,
+        Rule::Polish if integer && (2..=4).contains(&i10) && !(12..=14).contains(&i100) => Few,
+        Rule::Polish if integer => Many,
+        Rule::Czech if integer && i == 1 => One,
+        Rule::Czech if integer && (2..=4).contains(&i) => Few,
+        Rule::Czech if !integer => Many,
+        Rule::SerboCroatian if (integer && i10 == 1 && i100 != 11) || (f10 == 1 && f100 != 11) => {
+            One
+        }
+        Rule::SerboCroatian
+            if (integer && (2..=4).contains(&i10) && !(12..=14).contains(&i100))
+                || ((2..=4).contains(&f10) && !(12..=14).contains(&f100)) =>
+        {
+            Few
+        }
+        Rule::Romanian if integer && i == 1 => One,
+        Rule::Romanian if !integer || n == 0.0 || (2..=19).contains(&i100) => Few,
+        Rule::Hebrew if integer && i == 1 => One,
+        Rule::Hebrew if integer && i == 2 => Two,
+        Rule::Hebrew if integer && n > 10.0 && i10 == 0 => Many,
+        Rule::Arabic if n == 0.0 => Zero,
+        Rule::Arabic if n == 1.0 => One,
+        Rule::Arabic if n == 2.0 => Two,
+        Rule::Arabic if integer && (3..=10).contains(&i100) => Few,
+        Rule::Arabic if integer && (11..=99).contains(&i100) => Many,
+        Rule::Lithuanian if n10 == 1.0 && !in_range(n100, 11..=19) => One,
+        Rule::Lithuanian if in_range(n10, 2..=9) && !in_range(n100, 11..=19) => Few,
+        Rule::Lithuanian if f != 0 => Many,
+        Rule::Latvian
+            if n10 == 0.0 || in_range(n100, 11..=19) || (v == 2 && (11..=19).contains(&f100)) =>
+        {
+            Zero
+        }
+        Rule::Latvian if (n10 == 1.0 && n100 != 11.0) || (f10 == 1 && (v != 2 || f100 != 11)) => {
+            One
+        }
+        Rule::Slovenian if integer && i100 == 1 => One,
+        Rule::Slovenian if integer && i100 == 2 => Two,
+        Rule::Slovenian if !integer || (3..=4).contains(&i100) => Few,
+        Rule::Irish if n == 1.0 => One,
+        Rule::Irish if n == 2.0 => Two,
+        Rule::Irish if in_range(n, 3..=6) => Few,
+        Rule::Irish if in_range(n, 7..=10) => Many,
+        Rule::Welsh if n == 0.0 => Zero,
+        Rule::Welsh if n == 1.0 => One,
+        Rule::Welsh if n == 2.0 => Two,
+        Rule::Welsh if n == 3.0 => Few,
+        Rule::Welsh if n == 6.0 => Many,
+        Rule::Macedonian if (integer && i10 == 1 && i100 != 11) || (f10 == 1 && f100 != 11) => One,
+        Rule::Icelandic if (t == 0 && i10 == 1 && i100 != 11) || (t % 10 == 1 && t % 100 != 11) => {
+            One
+        }
+        _ => Other,
+    }
+}
+
+/// Select the translation to use for `count`, out of the `available` categories.
+///
+/// Like Ruby I18n, a `zero` translation is used for `0` in all languages.
+/// Falls back to `other` if the category of `count` is not available,
+/// or `count` has no operands.
+pub fn select<T: PluralOperand + ?Sized>(
+    locale: &str,
+    count: &T,
+    available: &[PluralCategory],
+) -> PluralCategory {
+    let Some(operands) = count.operands() else {
+        return PluralCategory::Other;
+    };
+    if operands.n == 0.0 && available.contains(&PluralCategory::Zero) {
+        return PluralCategory::Zero;
+    }
+    match category(locale, &operands) {
+        category if available.contains(&category) => category,
+        _ => PluralCategory::Other,
+    }
+}
@@ -0,0 +1,136 @@
+//! Translations referencing other keys of the same locale.
+//!
+//! A translation that is only `:common.ok`, a `:` followed by a dotted key,
+//! is replaced by the translation of `common.ok`. Other translations starting
+//! with `:` like `:Note` or `:D` are text. `%{t:common.ok}` within a
+//! translation is replaced by it as well, and can reference keys without dots.
+//! References are resolved when the translations are loaded, so they cost
+//! nothing at runtime.
+
+use crate::conflict::Origins;
+use crate::{Error, Result, TranslationMap};
+use std::collections::BTreeMap;
+
+/// The key referenced by a translation like `:common.ok`.
+fn alias(translation: &str) -> Option<&str> {
+    let key = translation.trim().strip_prefix(':')?;
+    let segment = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    };
+    (key.contains('.') && key.split('.').all(segment)).then_some(key)
+}
+
+/// Whether `translation` references any other key.
+fn has_references(translation: &str) -> bool {
+    alias(translation).is_some() || translation.contains("%{t:")
+}
+
+/// Replace all references in `tmap` by the translations they reference.
+///
+/// `origins` names the file of a reference that is part of a cycle or
+/// references a key without translation in its locale.
+pub(crate) fn resolve(tmap: &mut TranslationMap, origins: &Origins) -> Result<()> {
+    let mut resolver = Resolver {
+        tmap: &*tmap,
+        origins,
+        resolved: BTreeMap::new(),
+        stack: Vec::new(),
+    };
+    for (key, translations) in tmap.iter() {
+        for (locale, translation) in translations {
+            if has_references(translation) {
+                resolver.resolve(locale, key)?;
+            }
+        }
+    }
+    let resolved = resolver.resolved;
+    for ((locale, key), translation) in resolved {
+        if let Some(old) = tmap.get_mut(&key).and_then(|t| t.get_mut(&locale)) {
+            *old = translation;
+        }
+    }
+    Ok(())
+}
+
+struct Resolver<'a> {
+    tmap: &'a TranslationMap,
+    origins: &'a Origins,
+    /// Resolved translations by locale and key.
+    resolved: BTreeMap<(String, String), String>,
+    /// Keys being resolved, to detect cycles.
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// The translation of `key` in `locale` with all references resolved.
+    fn resolve(&mut self, locale: &str, key: &str) -> Result<String> {
+        let id = (locale.to_owned(), key.to_owned());
+        if let Some(resolved) = self.resolved.get(&id) {
+            return Ok(resolved.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|k| k == key) {
+            let mut cycle = self.stack[start..]
+                .iter()
+                .map(|key| format!("{locale}.{key}"))
+                .collect::<Vec<_>>();
+            cycle.push(format!("{locale}.{key}"));
+            return Err(Error::ReferenceCycle {
+                path: self.origins.file(&cycle[0]).unwrap_or_default(),
+                cycle,
+            });
+        }
+
+        let tmap = self.tmap;
+        let translation = &tmap[key][locale];
+        self.stack.push(key.to_owned());
+        let resolved = match alias(translation) {
+            Some(target) => self.target(locale, key, target)?,
+            None => self.interpolate(locale, key, translation)?,
+        };
+        self.stack.pop();
+        self.resolved.insert(id, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// Replace the `%{t:key}` references in `translation`, `%%` is kept as is.
+    fn interpolate(&mut self, locale: &str, key: &str, translation: &str) -> Result<String> {
+        let mut resolved = String::with_capacity(translation.len());
+        let mut rest = translation;
+        while let Some(idx) = rest.find('%') {
+            let (text, tail) = rest.split_at(idx);
+            resolved.push_str(text);
+            let reference = tail
+                .strip_prefix("%{t:")
+                .and_then(|tail| tail.find('}').map(|end| &tail[..end]));
+            if let Some(target) = reference {
+                resolved.push_str(&self.target(locale, key, target)?);
+                rest = &tail[target.len() + 5..];
+            } else {
+                let len = if tail.starts_with("%%") { 2 } else { 1 };
+                resolved.push_str(&tail[..len]);
+                rest = &tail[len..];
+            }
+        }
+        resolved.push_str(rest);
+        Ok(resolved)
+    }
+
+    /// The resolved translation of `target`, referenced by `key`.
+    fn target(&mut self, locale: &str, key: &str, target: &str) -> Result<String> {
+        let exists = self
+            .tmap
+            .get(target)
+            .is_some_and(|translations| translations.contains_key(locale));
+        if !exists {
+            let key = format!("{locale}.{key}");
+            return Err(Error::DanglingReference {
+                path: self.origins.file(&key).unwrap_or_default(),
+                key,
+                target: target.to_owned(),
+            });
+        }
+        self.resolve(locale, target)
+    }
+}
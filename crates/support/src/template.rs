@@ -0,0 +1,82 @@
+//! Ruby style `%{name}` translation templates.
+//!
+//! A template is literal text with `%{name}` placeholders,
+//! where `name` consists of alphanumeric characters and `_`.
+//! `%%` is an escaped `%`, anything else is taken literally.
+
+use std::fmt::{self, Write};
+
+/// A single piece of a parsed template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Piece<'a> {
+    /// Literal text, to be emitted verbatim.
+    Text(&'a str),
+    /// A `%{name}` placeholder, carrying `name`.
+    Placeholder(&'a str),
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Split a template into literal text and placeholders.
+pub fn parse(template: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+
+    while let Some(idx) = rest.find('%') {
+        let (text, tail) = rest.split_at(idx);
+        if let Some(after) = tail.strip_prefix("%%") {
+            pieces.push(Piece::Text(&rest[..idx + 1]));
+            rest = after;
+            continue;
+        }
+        let placeholder = tail
+            .strip_prefix("%{")
+            .and_then(|tail| tail.find('}').map(|end| &tail[..end]))
+            .filter(|name| is_placeholder_name(name));
+        if let Some(name) = placeholder {
+            if !text.is_empty() {
+                pieces.push(Piece::Text(text));
+            }
+            pieces.push(Piece::Placeholder(name));
+            rest = &tail[name.len() + 3..];
+        } else {
+            pieces.push(Piece::Text(&rest[..idx + 1]));
+            rest = &tail[1..];
+        }
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    pieces
+}
+
+/// All placeholder names used in a template, in order of appearance.
+pub fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    parse(template).into_iter().filter_map(|piece| match piece {
+        Piece::Placeholder(name) => Some(name),
+        Piece::Text(_) => None,
+    })
+}
+
+/// Render a template at runtime.
+///
+/// Placeholders without a matching argument are kept verbatim.
+pub fn interpolate(template: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    for piece in parse(template) {
+        match piece {
+            Piece::Text(text) => rendered.push_str(text),
+            Piece::Placeholder(name) => match args.iter().find(|(n, _)| *n == name) {
+                Some((_, value)) => {
+                    let _ = write!(rendered, "{value}");
+                }
+                None => {
+                    let _ = write!(rendered, "%{{{name}}}");
+                }
+            },
+        }
+    }
+    rendered
+}
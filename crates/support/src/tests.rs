@@ -1,29 +1,118 @@
 use super::*;
-use crate::{prepare, trans_map_voodoo};
+use crate::config::{I18nConfig, Layout, Severity};
+use crate::conflict::{ConflictKind, Origins};
+use crate::{trans_map_voodoo, write_catalog};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+fn config(conflicts: Severity) -> I18nConfig {
+    I18nConfig {
+        conflicts,
+        ..Default::default()
+    }
+}
+
 fn test_locale_dir() -> PathBuf {
-    std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../foo/locales/foo-bar-baz")
+    std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../examples/foo/locales")
+}
+
+/// An empty temporary directory, removed again on drop, also if the test panics.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("rust-i18n-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
 }
 
 #[test]
 fn full_roundtrip() {
     let dir = test_locale_dir();
-    prepare(&dir).unwrap();
+    let out_dir = TempDir::new("out");
+
+    let path = write_catalog(&dir, &out_dir, &config(Severity::Deny)).unwrap();
+    assert_eq!(path, out_dir.join(CATALOG_FILE_NAME));
+    assert!(!dir.join(CATALOG_FILE_NAME).exists());
 
-    dbg!(super::locales_yaml_files_to_translation_map(&dir).unwrap());
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(
+        deserialize(&bytes).unwrap(),
+        super::locales_yaml_files_to_translation_map(&dir).unwrap()
+    );
 }
 
 #[test]
 fn ser_de_roundtrip() {
-    let tp2per_locale_translations = HashMap::new();
-    let bytes = crate::serialize(tp2per_locale_translations.clone()).unwrap();
+    let tp2per_locale_translations = BTreeMap::new();
+    let bytes = crate::serialize(tp2per_locale_translations.clone(), 0).unwrap();
     let reconstructed = crate::deserialize(&bytes[..]).unwrap();
     assert_eq!(tp2per_locale_translations, reconstructed);
 }
 
+#[test]
+fn catalog_header_is_checked() {
+    let mut tmap = TranslationMap::new();
+    tmap.entry("hello".to_owned())
+        .or_default()
+        .insert("en".to_owned(), "Hello".to_owned());
+    let bytes = crate::serialize(tmap.clone(), 42).unwrap();
+
+    let (header, _) = blob::read_header(&bytes).unwrap();
+    assert_eq!(
+        header,
+        blob::Header {
+            version: blob::FORMAT_VERSION,
+            content_hash: 42,
+            locales: vec!["en".to_owned()],
+        }
+    );
+    assert_eq!(crate::deserialize(&bytes).unwrap(), tmap);
+
+    assert!(matches!(
+        crate::deserialize(&bytes[1..]),
+        Err(Error::NotACatalog)
+    ));
+
+    let mut newer = bytes.clone();
+    newer[blob::MAGIC.len()] = blob::FORMAT_VERSION as u8 + 1;
+    let err = crate::deserialize(&newer).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Catalog file format version {} is not supported, expected {}; rebuild with the same rust-i18n-support version as rust-i18n",
+            blob::FORMAT_VERSION + 1,
+            blob::FORMAT_VERSION
+        )
+    );
+
+    let dir = test_locale_dir();
+    assert!(matches!(
+        crate::deserialize_fresh(&bytes, &dir),
+        Err(Error::Stale { .. })
+    ));
+    let content_hash = blob::content_hash(&dir, &locale_files(&dir).unwrap()).unwrap();
+    let bytes = crate::serialize(tmap, content_hash).unwrap();
+    crate::deserialize_fresh(&bytes, &dir).unwrap();
+}
+
 #[test]
 fn yaml_parsing_works() {
     let yaml_content = r###"---
@@ -32,14 +121,22 @@ en:
   "Use YAML for mapping localized text, and support mutiple YAML files merging.": ""
   a.very.nested.message: whatever
 "###;
-    let mut trans_map = HashMap::new();
-    extract_yaml_content(yaml_content, &mut trans_map);
+    let mut trans_map = BTreeMap::new();
+    let path = Path::new("en.yml");
+    let trs = parse_file(
+        Path::new(""),
+        path,
+        yaml_content.as_bytes(),
+        &I18nConfig::default(),
+    )
+    .unwrap();
+    extract_content(path, trs, &mut trans_map, &mut Origins::default()).unwrap();
     dbg!(trans_map);
 }
 
 #[test]
 fn trans_map_voodoo_works() {
-    let mut json_intermediate = HashMap::new();
+    let mut json_intermediate = BTreeMap::new();
     json_intermediate.insert(
         "en".to_owned(),
         json!({
@@ -57,3 +154,683 @@ fn trans_map_voodoo_works() {
         Some(&"w00t".to_owned())
     );
 }
+
+#[test]
+fn template_parsing_works() {
+    use crate::template::{parse, placeholders, Piece};
+
+    assert_eq!(
+        parse("Hello, %{name}. Your message is: %{msg}"),
+        vec![
+            Piece::Text("Hello, "),
+            Piece::Placeholder("name"),
+            Piece::Text(". Your message is: "),
+            Piece::Placeholder("msg"),
+        ]
+    );
+    assert_eq!(
+        parse("100%% of {braces}, %{} and %{not a name}"),
+        vec![
+            Piece::Text("100%"),
+            Piece::Text(" of {braces}, %"),
+            Piece::Text("{} and %"),
+            Piece::Text("{not a name}"),
+        ]
+    );
+    assert_eq!(
+        parse("%{count}%"),
+        vec![Piece::Placeholder("count"), Piece::Text("%")]
+    );
+    assert_eq!(
+        placeholders("%{a} %{b_1} %%{c} %{a}").collect::<Vec<_>>(),
+        vec!["a", "b_1", "a"]
+    );
+}
+
+#[test]
+fn plural_rules_work() {
+    use crate::plural::{category, select, PluralCategory::*, PluralOperand, PluralOperands};
+
+    let cat =
+        |locale: &str, count: &dyn PluralOperand| category(locale, &count.operands().unwrap());
+    assert_eq!(cat("en", &1), One);
+    assert_eq!(cat("en-US", &2), Other);
+    assert_eq!(cat("en", &"1.0"), Other);
+    assert_eq!(cat("fr", &0), One);
+    assert_eq!(cat("fr", &1.5f64), One);
+    assert_eq!(cat("fr", &2_000_000), Many);
+    assert_eq!(cat("es", &1_000_000u64), Many);
+    assert_eq!(cat("it", &"1000000.0"), Other);
+    assert_eq!(cat("pt", &0), One);
+    assert_eq!(cat("pt-PT", &0), Other);
+    assert_eq!(cat("ja", &1), Other);
+    assert_eq!(cat("ru", &21), One);
+    assert_eq!(cat("ru", &22u8), Few);
+    assert_eq!(cat("ru", &12), Many);
+    assert_eq!(cat("ru", &(-5i64)), Many);
+    assert_eq!(cat("ru", &"1.5"), Other);
+    assert_eq!(cat("pl", &1), One);
+    assert_eq!(cat("pl", &21), Many);
+    assert_eq!(cat("cs", &3), Few);
+    assert_eq!(cat("ar", &0), Zero);
+    assert_eq!(cat("ar", &2), Two);
+    assert_eq!(cat("ar", &103), Few);
+    assert_eq!(cat("ar", &111), Many);
+    assert_eq!(cat("lt", &21), One);
+    assert_eq!(cat("lt", &12), Other);
+    assert_eq!(cat("lt", &"0.5"), Many);
+    assert_eq!(cat("lv", &10), Zero);
+    assert_eq!(cat("lv", &21), One);
+    assert_eq!(cat("sl", &102), Two);
+    assert_eq!(cat("sl", &"1.5"), Few);
+    assert_eq!(cat("ga", &7), Many);
+    assert_eq!(cat("cy", &6), Many);
+    assert_eq!(cat("mk", &11), Other);
+    assert_eq!(cat("is", &21), One);
+    assert_eq!(cat("xx", &1), Other);
+
+    assert_eq!(
+        PluralOperands::from_decimal("-1.50"),
+        Some(PluralOperands {
+            n: 1.5,
+            i: 1,
+            v: 2,
+            w: 1,
+            f: 50,
+            t: 5
+        })
+    );
+    assert_eq!(PluralOperands::from_decimal("many"), None);
+    assert_eq!(PluralOperands::from_decimal("100000000000000000000"), None);
+    assert_eq!(u128::MAX.operands(), None);
+    assert_eq!(f64::NAN.operands(), None);
+
+    assert_eq!(select("en", &0, &[Zero, One, Other]), Zero);
+    assert_eq!(select("en", &0, &[One, Other]), Other);
+    assert_eq!(select("en", &1, &[One, Other]), One);
+    assert_eq!(select("ru", &2, &[One, Other]), Other);
+    assert_eq!(select("en", &1e21f64, &[Zero, One, Other]), Other);
+    assert_eq!(select("en", &"many", &[Zero, One, Other]), Other);
+}
+
+#[test]
+fn catalog_translates_at_runtime() {
+    use crate::template::interpolate;
+    use crate::Catalog;
+
+    assert_eq!(
+        interpolate("%{name}: 100%% {x} %{missing}", &[("name", &"Jason")]),
+        "Jason: 100% {x} %{missing}"
+    );
+
+    static CATALOG: Catalog = Catalog {
+        default_locale: "en",
+        locales: &["en", "zh-HK", "zh-Hant"],
+        fallbacks: &[("zh-HK", &["zh-Hant"])],
+        translations: &[
+            (
+                "hello",
+                &[("en", "Hello, %{name}!"), ("zh-Hant", "妳好, %{name}!")],
+            ),
+            ("messages.one", &[("en", "One message")]),
+            ("messages.other", &[("en", "%{count} messages")]),
+        ],
+    };
+
+    let name = "Jason";
+    assert_eq!(
+        CATALOG.translate("en-US", "hello", &[("name", &name)]),
+        "Hello, Jason!"
+    );
+    assert_eq!(
+        CATALOG.translate("zh-HK", "hello", &[("name", &name)]),
+        "妳好, Jason!"
+    );
+    assert_eq!(
+        CATALOG.translate("de", "messages", &[("count", &1)]),
+        "One message"
+    );
+    assert_eq!(
+        CATALOG.translate("de", "messages", &[("count", &7)]),
+        "7 messages"
+    );
+    assert_eq!(CATALOG.translate("en", "messages", &[]), "messages");
+    assert_eq!(CATALOG.translate("en", "missing.key", &[]), "missing.key");
+
+    assert_eq!(CATALOG.resolve_locale("zh-HK"), Some("zh-HK"));
+    assert_eq!(CATALOG.resolve_locale("zh-Hant-TW"), Some("zh-Hant"));
+    assert_eq!(CATALOG.resolve_locale("en-US"), Some("en"));
+    // The default locale is not a fallback of every locale here.
+    assert_eq!(CATALOG.resolve_locale("de"), None);
+}
+
+#[test]
+fn arrays_are_kept_by_index() {
+    use crate::Catalog;
+
+    let vars = extract_vars(
+        "en",
+        &json!({ "date": { "day_names": ["Sunday", "Monday"] }, "options": [{ "label": "On" }] }),
+    );
+    assert_eq!(vars["en.date.day_names.0"], "Sunday");
+    assert_eq!(vars["en.date.day_names.1"], "Monday");
+    assert_eq!(vars["en.options.0.label"], "On");
+    assert!(!vars.contains_key("en.date.day_names"));
+
+    static CATALOG: Catalog = Catalog {
+        default_locale: "en",
+        locales: &["de", "en"],
+        fallbacks: &[],
+        translations: &[
+            ("date.day_names.0", &[("de", "Sonntag"), ("en", "Sunday")]),
+            ("date.day_names.1", &[("de", "Montag"), ("en", "Monday")]),
+        ],
+    };
+    assert_eq!(CATALOG.list("de", "date.day_names"), ["Sonntag", "Montag"]);
+    assert_eq!(CATALOG.list("fr", "date.day_names"), ["Sunday", "Monday"]);
+    assert!(CATALOG.list("en", "date.month_names").is_empty());
+}
+
+#[test]
+fn catalog_is_reproducible() {
+    let dir = test_locale_dir();
+    let serialize_dir = || {
+        let tmap = super::locales_yaml_files_to_translation_map(&dir).unwrap();
+        crate::serialize(tmap, 0).unwrap()
+    };
+    assert_eq!(serialize_dir(), serialize_dir());
+}
+
+#[test]
+fn loading_errors_name_the_file() {
+    let dir = TempDir::new("errors");
+    let path = dir.join("en.yml");
+
+    fs::write(&path, "en:\n  hello: Hello\n  bad: [unclosed\n").unwrap();
+    let err = locales_yaml_files_to_translation_map(&dir).unwrap_err();
+    match &err {
+        Error::Parse {
+            path: err_path,
+            line,
+            column,
+            ..
+        } => {
+            assert_eq!(err_path, &path);
+            assert_eq!((*line, *column), (Some(4), Some(1)));
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+    assert!(err
+        .to_string()
+        .starts_with(&format!("Failed to parse {}:4:1: ", path.display())));
+
+    fs::write(&path, "en: Hello\n").unwrap();
+    let err = locales_yaml_files_to_translation_map(&dir).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Invalid locale `en` in {}: expected a mapping of keys to translations",
+            path.display()
+        )
+    );
+}
+
+#[test]
+fn conflicting_keys_are_detected() {
+    let dir = TempDir::new("conflicts");
+    fs::write(
+        dir.join("en.yml"),
+        "en:\n  hello: Hello\n  same: Same\n  view:\n    buttons:\n      ok: OK\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("view.en.yml"),
+        "en:\n  hello:\n    world: Hello world\n  same: Same\n  view:\n    buttons:\n      ok: Okay\n",
+    )
+    .unwrap();
+
+    let (tmap, conflicts) = load_locales(&dir, &I18nConfig::default()).unwrap();
+    assert_eq!(tmap["view.buttons.ok"]["en"], "Okay");
+    assert_eq!(tmap["hello.world"]["en"], "Hello world");
+    assert_eq!(
+        conflicts
+            .iter()
+            .map(|c| (c.key.as_str(), c.kind))
+            .collect::<Vec<_>>(),
+        vec![
+            ("en.hello", ConflictKind::LeafAndSubtree),
+            ("en.view.buttons.ok", ConflictKind::Value),
+        ]
+    );
+    assert_eq!(
+        conflicts[1].to_string(),
+        format!(
+            "`en.view.buttons.ok` is defined in {} and {} with different values, the latter wins",
+            dir.join("en.yml").display(),
+            dir.join("view.en.yml").display()
+        )
+    );
+
+    let out_dir = dir.join("out");
+    fs::create_dir_all(&out_dir).unwrap();
+    assert!(matches!(
+        write_catalog(&dir, &out_dir, &config(Severity::Deny)),
+        Err(Error::Conflicts(c)) if c == conflicts
+    ));
+    write_catalog(&dir, &out_dir, &config(Severity::Allow)).unwrap();
+}
+
+#[test]
+fn locale_file_formats_can_be_mixed() {
+    let dir = TempDir::new("formats");
+    fs::write(dir.join("en.yaml"), "en:\n  hello: Hello\n").unwrap();
+    fs::write(
+        dir.join("de.json"),
+        r#"{"de": {"hello": "Hallo", "messages": {"one": "Eine Nachricht"}}}"#,
+    )
+    .unwrap();
+    fs::write(dir.join("fr.toml"), "[fr]\nhello = \"Bonjour\"\n").unwrap();
+    fs::write(dir.join("notes.txt"), "not a locale file").unwrap();
+
+    assert_eq!(
+        locale_files(&dir).unwrap(),
+        vec![
+            dir.join("de.json"),
+            dir.join("en.yaml"),
+            dir.join("fr.toml")
+        ]
+    );
+    let tmap = locales_yaml_files_to_translation_map(&dir).unwrap();
+    assert_eq!(
+        tmap["hello"],
+        BTreeMap::from([
+            ("de".to_owned(), "Hallo".to_owned()),
+            ("en".to_owned(), "Hello".to_owned()),
+            ("fr".to_owned(), "Bonjour".to_owned()),
+        ])
+    );
+    assert_eq!(tmap["messages.one"]["de"], "Eine Nachricht");
+
+    fs::write(dir.join("fr.toml"), "[fr]\nhello = \n").unwrap();
+    match locales_yaml_files_to_translation_map(&dir).unwrap_err() {
+        Error::Parse { path, line, .. } => {
+            assert_eq!(path, dir.join("fr.toml"));
+            assert_eq!(line, Some(2));
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+}
+
+/// A little-endian `.mo` file of `(original, translation)` pairs, sorted.
+fn mo_file(messages: &[(&str, &str)]) -> Vec<u8> {
+    let count = messages.len() as u32;
+    let (originals, translations) = (28, 28 + 8 * count);
+    let mut strings_at = 28 + 16 * count;
+    let (mut tables, mut strings) = (Vec::new(), Vec::new());
+    for table in [0, 1] {
+        for message in messages {
+            let s = if table == 0 { message.0 } else { message.1 };
+            tables.extend((s.len() as u32).to_le_bytes());
+            tables.extend(strings_at.to_le_bytes());
+            strings.extend(s.as_bytes());
+            strings.push(0);
+            strings_at += s.len() as u32 + 1;
+        }
+    }
+    let mut bytes = Vec::new();
+    for word in [0x9504_12de, 0, count, originals, translations, 0, 0] {
+        bytes.extend(u32::to_le_bytes(word));
+    }
+    bytes.extend(tables);
+    bytes.extend(strings);
+    bytes
+}
+
+#[test]
+fn gettext_catalogs_are_imported() {
+    let dir = TempDir::new("gettext");
+    fs::create_dir_all(dir.join("ru/LC_MESSAGES")).unwrap();
+    fs::write(
+        dir.join("ru/LC_MESSAGES/app.po"),
+        r#"
+# Translator comment
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2);\n"
+
+msgid "Hello"
+msgstr "Привет"
+
+msgctxt "menu"
+msgid "Open"
+msgstr ""
+"Откр"
+"ыть"
+
+#, c-format
+msgid "%{count} file"
+msgid_plural "%{count} files"
+msgstr[0] "%{count} файл"
+msgstr[1] "%{count} файла"
+msgstr[2] "%{count} файлов"
+
+#, fuzzy
+msgid "Close"
+msgstr "Закрыть"
+
+msgid "Quit"
+msgstr ""
+
+#~ msgid "Obsolete"
+#~ msgstr "Устарело"
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("messages.mo"),
+        mo_file(&[
+            (
+                "",
+                "Language: de\nContent-Type: text/plain; charset=UTF-8\n",
+            ),
+            (
+                "%{count} file\0%{count} files",
+                "%{count} Datei\0%{count} Dateien",
+            ),
+            ("Hello", "Hallo"),
+            ("menu\x04Open", "Öffnen"),
+        ]),
+    )
+    .unwrap();
+
+    let tmap = locales_yaml_files_to_translation_map(&dir).unwrap();
+    assert_eq!(tmap["Hello"]["ru"], "Привет");
+    assert_eq!(tmap["Hello"]["de"], "Hallo");
+    assert_eq!(tmap["menu.Open"]["ru"], "Открыть");
+    assert_eq!(tmap["menu.Open"]["de"], "Öffnen");
+    assert_eq!(tmap["%{count} file.one"]["ru"], "%{count} файл");
+    assert_eq!(tmap["%{count} file.few"]["ru"], "%{count} файла");
+    assert_eq!(tmap["%{count} file.many"]["ru"], "%{count} файлов");
+    assert_eq!(tmap["%{count} file.other"]["ru"], "%{count} файлов");
+    assert_eq!(tmap["%{count} file.one"]["de"], "%{count} Datei");
+    assert_eq!(tmap["%{count} file.other"]["de"], "%{count} Dateien");
+    // Fuzzy, untranslated and obsolete messages are skipped.
+    assert!(!tmap.contains_key("Close"));
+    assert!(!tmap.contains_key("Quit"));
+    assert!(!tmap.contains_key("Obsolete"));
+    assert!(!tmap.contains_key(""));
+
+    fs::write(
+        dir.join("ru/LC_MESSAGES/app.po"),
+        "msgid \"Hello\"\nmsgstr \"Привет\"\nmsgstr \"Здравствуйте\"\n",
+    )
+    .unwrap();
+    let err = locales_yaml_files_to_translation_map(&dir).unwrap_err();
+    match &err {
+        Error::Parse { path, line, .. } => {
+            assert_eq!(path, &dir.join("ru/LC_MESSAGES/app.po"));
+            assert_eq!(*line, Some(3));
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+    // The syntax error itself has no line, the message still does.
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Failed to parse {}:3:1: unexpected `msgstr`",
+            dir.join("ru/LC_MESSAGES/app.po").display()
+        )
+    );
+    fs::remove_file(dir.join("ru/LC_MESSAGES/app.po")).unwrap();
+
+    // Each CLDR category gets the form the plural expression selects for its
+    // integers, so French and Spanish `many` for millions is `other`.
+    let plural_catalog = |language: &str, plural_forms: &str| {
+        format!(
+            "msgid \"\"\nmsgstr \"\"\n\"Language: {language}\\n\"\n\"Plural-Forms: {plural_forms}\\n\"\n\n\
+            msgid \"file\"\nmsgid_plural \"files\"\nmsgstr[0] \"a\"\nmsgstr[1] \"b\"\n"
+        )
+    };
+    let path = dir.join("app.po");
+    for (language, plural_forms) in [
+        ("fr", "nplurals=2; plural=(n > 1);"),
+        ("es", "nplurals=2; plural=(n != 1);"),
+    ] {
+        fs::write(&path, plural_catalog(language, plural_forms)).unwrap();
+        let tmap = locales_yaml_files_to_translation_map(&dir).unwrap();
+        assert_eq!(tmap["file.one"][language], "a");
+        assert_eq!(tmap["file.many"][language], "b");
+        assert_eq!(tmap["file.other"][language], "b");
+    }
+    // Without Plural-Forms, `n != 1` as for gettext.
+    fs::write(
+        &path,
+        "msgid \"\"\nmsgstr \"Language: ja\\n\"\n\nmsgid \"file\"\nmsgid_plural \"files\"\nmsgstr[0] \"a\"\nmsgstr[1] \"b\"\n",
+    )
+    .unwrap();
+    let tmap = locales_yaml_files_to_translation_map(&dir).unwrap();
+    assert_eq!(tmap["file.other"]["ja"], "b");
+
+    fs::write(
+        &path,
+        plural_catalog("ru", "nplurals=2; plural=(n%10==1 ? 0 : 2);"),
+    )
+    .unwrap();
+    let err = locales_yaml_files_to_translation_map(&dir).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Failed to parse {}: Plural-Forms has nplurals=2, but its plural expression has no form for n = 2",
+            path.display()
+        )
+    );
+    fs::write(&path, plural_catalog("xx", "nplurals=2; plural=(n != 1;")).unwrap();
+    assert!(matches!(
+        locales_yaml_files_to_translation_map(&dir),
+        Err(Error::Parse { .. })
+    ));
+    fs::write(&path, plural_catalog("xx", "plural=0;")).unwrap();
+    let err = locales_yaml_files_to_translation_map(&dir).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("Invalid Plural-Forms `plural=0;`"));
+}
+
+#[test]
+fn gettext_contexts_must_not_be_message_ids() {
+    let dir = TempDir::new("gettext-context");
+    let path = dir.join("de.po");
+    let message = "msgid \"File\"\nmsgstr \"Datei\"\n";
+    let in_context = "msgctxt \"File\"\nmsgid \"Open\"\nmsgstr \"Öffnen\"\n";
+    for catalog in [
+        format!("{message}\n{in_context}"),
+        format!("{in_context}\n{message}"),
+    ] {
+        fs::write(&path, catalog).unwrap();
+        let err = locales_yaml_files_to_translation_map(&dir).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Failed to parse {}: `msgctxt \"File\"` is also a `msgid` without context",
+                path.display()
+            )
+        );
+    }
+}
+
+#[test]
+fn locales_can_come_from_paths() {
+    let dir = TempDir::new("layout");
+    fs::create_dir_all(dir.join("de/admin")).unwrap();
+    fs::write(dir.join("de/views.yml"), "title: Titel\n").unwrap();
+    fs::write(dir.join("de/admin/users.yml"), "title: Benutzer\n").unwrap();
+    fs::write(dir.join("views.fr.yml"), "title: Titre\n").unwrap();
+    fs::write(dir.join("common.en.json"), r#"{"hello": "Hello"}"#).unwrap();
+
+    let mut config = I18nConfig {
+        layout: Layout::Path,
+        ..Default::default()
+    };
+    let (tmap, conflicts) = load_locales(&dir, &config).unwrap();
+    assert_eq!(tmap["hello"]["en"], "Hello");
+    assert_eq!(tmap["title"]["fr"], "Titre");
+    // Without namespaces, both German files define `title`.
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].key, "de.title");
+
+    config.namespaces = true;
+    let (tmap, conflicts) = load_locales(&dir, &config).unwrap();
+    assert!(conflicts.is_empty());
+    assert_eq!(tmap["common.hello"]["en"], "Hello");
+    assert_eq!(tmap["views.title"]["de"], "Titel");
+    assert_eq!(tmap["views.title"]["fr"], "Titre");
+    assert_eq!(tmap["admin.users.title"]["de"], "Benutzer");
+
+    // A file directly in the locale directory needs a locale suffix.
+    let path = dir.join("views.yml");
+    fs::write(&path, "title: Title\n").unwrap();
+    let err = load_locales(&dir, &config).unwrap_err();
+    assert!(matches!(&err, Error::NoLocale { path: p } if *p == path));
+}
+
+#[test]
+fn references_and_anchors_are_resolved() {
+    let dir = TempDir::new("references");
+    fs::write(
+        dir.join("en.yml"),
+        r#"
+en:
+  common:
+    ok: Ok
+    cancel: Cancel
+  buttons: &buttons
+    ok: :common.ok
+    cancel: ":common.cancel"
+  dialog:
+    <<: *buttons
+    cancel: Close
+    title: Press %{t:buttons.ok} to continue, 100%% %{t:common.cancel}ed %{name}
+  alert: *buttons
+  faces: ":)"
+  note: ":Note"
+  grin: :D
+"#,
+    )
+    .unwrap();
+    fs::write(dir.join("de.yml"), "de:\n  common:\n    ok: :buttons.ok\n").unwrap();
+    fs::write(dir.join("fr.yml"), "fr:\n  buttons:\n    ok: :common.ok\n").unwrap();
+
+    // The French `common.ok` is missing, references stay within their locale.
+    match locales_yaml_files_to_translation_map(&dir).unwrap_err() {
+        Error::DanglingReference { path, key, target } => {
+            assert_eq!(path, dir.join("fr.yml"));
+            assert_eq!(key, "fr.buttons.ok");
+            assert_eq!(target, "common.ok");
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+
+    fs::write(dir.join("fr.yml"), "fr:\n  common:\n    ok: D'accord\n").unwrap();
+    // The German `buttons.ok` is missing as well.
+    fs::write(
+        dir.join("de.yml"),
+        "de:\n  common:\n    ok: :buttons.ok\n  buttons:\n    ok: '%{t:common.ok}!'\n",
+    )
+    .unwrap();
+    match locales_yaml_files_to_translation_map(&dir).unwrap_err() {
+        err @ Error::ReferenceCycle { .. } => {
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "References in {} form a cycle: de.buttons.ok -> de.common.ok -> de.buttons.ok",
+                    dir.join("de.yml").display()
+                )
+            );
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+
+    fs::remove_file(dir.join("de.yml")).unwrap();
+    let tmap = locales_yaml_files_to_translation_map(&dir).unwrap();
+    assert_eq!(tmap["buttons.ok"]["en"], "Ok");
+    assert_eq!(tmap["buttons.cancel"]["en"], "Cancel");
+    assert_eq!(tmap["dialog.ok"]["en"], "Ok");
+    assert_eq!(tmap["dialog.cancel"]["en"], "Close");
+    assert_eq!(
+        tmap["dialog.title"]["en"],
+        "Press Ok to continue, 100%% Canceled %{name}"
+    );
+    assert_eq!(tmap["alert.cancel"]["en"], "Cancel");
+    assert_eq!(tmap["faces"]["en"], ":)");
+    // Only `:` followed by a dotted key is a reference.
+    assert_eq!(tmap["note"]["en"], ":Note");
+    assert_eq!(tmap["grin"]["en"], ":D");
+    assert!(!tmap.contains_key("dialog.<<"));
+
+    // A typo in a reference is an error rather than text.
+    fs::write(dir.join("fr.yml"), "fr:\n  common:\n    ok: :comon.ok\n").unwrap();
+    match locales_yaml_files_to_translation_map(&dir).unwrap_err() {
+        Error::DanglingReference { path, key, target } => {
+            assert_eq!(path, dir.join("fr.yml"));
+            assert_eq!(key, "fr.common.ok");
+            assert_eq!(target, "comon.ok");
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+}
+
+#[test]
+fn locales_are_canonicalised() {
+    use crate::locale::canonicalize;
+
+    for (tag, canonical) in [
+        ("en", "en"),
+        ("zh_CN", "zh-CN"),
+        ("zh-cn", "zh-CN"),
+        ("ZH-HANT-tw", "zh-Hant-TW"),
+        ("es-419", "es-419"),
+        ("de-CH-1996", "de-CH-1996"),
+        ("sl-rozaj-biske", "sl-rozaj-biske"),
+        ("en-US-u-CA-gregory", "en-US-u-ca-gregory"),
+        ("zh-yue-HK", "zh-yue-HK"),
+        ("x-Klingon", "x-klingon"),
+        ("en-x-US", "en-x-us"),
+    ] {
+        assert_eq!(Locale::parse(tag).unwrap(), canonical, "{tag}");
+        assert_eq!(canonicalize(tag), canonical, "{tag}");
+    }
+    for tag in [
+        "",
+        "e",
+        "en-languages",
+        "en-",
+        "en US",
+        "en-US-u",
+        "en-x",
+        "12",
+    ] {
+        assert!(Locale::parse(tag).is_err(), "{tag}");
+        assert_eq!(canonicalize(tag), tag);
+    }
+    assert!(matches!(
+        canonicalize("zh-CN"),
+        std::borrow::Cow::Borrowed(_)
+    ));
+
+    let dir = TempDir::new("locales");
+    fs::write(dir.join("zh-CN.yml"), "zh-CN:\n  hello: 你好\n").unwrap();
+    fs::write(dir.join("zh_cn.yml"), "zh_cn:\n  bye: 再见\n").unwrap();
+    let tmap = locales_yaml_files_to_translation_map(&dir).unwrap();
+    assert_eq!(tmap["hello"]["zh-CN"], "你好");
+    assert_eq!(tmap["bye"]["zh-CN"], "再见");
+
+    fs::write(dir.join("en.yml"), "english language:\n  hello: Hello\n").unwrap();
+    match locales_yaml_files_to_translation_map(&dir).unwrap_err() {
+        Error::InvalidLocale { path, source } => {
+            assert_eq!(path, dir.join("en.yml"));
+            assert_eq!(source.tag, "english language");
+        }
+        err => panic!("Unexpected error {err:?}"),
+    }
+}
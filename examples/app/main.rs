@@ -1,6 +1,3 @@
-use rust_i18n::format_t;
-use rust_i18n::locale;
-
 // Init translations for current crate.
 rust_i18n::i18n!("examples/app/locales");
 
@@ -8,7 +5,9 @@ fn main() {}
 
 #[test]
 fn test_example_app() {
-    rust_i18n::set_locale("en");
+    use rust_i18n::{format_t, list_t};
+
+    rust_i18n::set_locale("en").unwrap();
     assert_eq!(
         format_t!("hello", name = "Longbridge"),
         "Hello, Longbridge!"
@@ -20,11 +19,17 @@ fn test_example_app() {
         "about 10 hours"
     );
 
+    assert_eq!(
+        list_t!("view.datetime.day_names"),
+        ["Sunday", "Monday", "Tuesday"]
+    );
+    assert_eq!(format_t!("view.datetime.day_names.1"), "Monday");
+
     assert_eq!(
         format_t!("hello", locale = "fr", name = "Longbridge"),
         "Bonjour, Longbridge!"
     );
-    rust_i18n::set_locale("fr");
+    rust_i18n::set_locale("fr").unwrap();
     assert_eq!(
         format_t!("hello", name = "Longbridge"),
         "Bonjour, Longbridge!"
@@ -33,4 +38,12 @@ fn test_example_app() {
         format_t!("view.datetime.about_x_hours", count = "10"),
         "environ 10 heures"
     );
+    assert_eq!(
+        list_t!("view.datetime.day_names", locale = "en"),
+        ["Sunday", "Monday", "Tuesday"]
+    );
+    assert_eq!(
+        list_t!("view.datetime.day_names"),
+        ["dimanche", "lundi", "mardi"]
+    );
 }
@@ -1,5 +1,7 @@
 use rust_i18n::format_t;
 
+rust_i18n::i18n!("locales");
+
 pub fn f() -> String {
     format_t!("hello")
 }
@@ -60,15 +60,25 @@
 //! You can use `rust_i18n::set_locale` to change the current locale in runtime.
 //!
 //! ```rs
-//! rust_i18n::set_locale("zh-CN");
+//! rust_i18n::set_locale("zh-CN")?;
 //! rust_i18n::locale();
 //! // => "zh-CN"
 //! ```
 //!
+//! Or override it on the current thread only, i.e. per request of a web server.
+//!
+//! ```rs
+//! rust_i18n::with_locale("de", || format_t!("hello"))?;
+//! // => "Hallo Welt!"
+//! ```
+//!
 //!
 
 // include!(concat!(env!("OUT_DIR"), "/i18n.rs"));
 use once_cell::sync::Lazy;
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
 use std::sync::Mutex;
 
 /// Format using an i18n translated format string.
@@ -80,28 +90,217 @@ use std::sync::Mutex;
 /// format_t!("greeting", locale = "de"); // greeting: "Hallo Welt!" => "Hallo Welt!"
 ///
 /// // With variables
-/// format_t!("messages.hello", "world"); // messages.hello: "Hello, {}" => "Hello, world"
-/// format_t!("messages.foo", "Foo", "Bar"); // messages.foo: "Hello, {} and {}" => "Hello, Foo and Bar"
+/// format_t!("messages.hello", name = "world"); // messages.hello: "Hello, %{name}" => "Hello, world"
+/// format_t!("messages.foo", a = "Foo", b = "Bar"); // messages.foo: "Hello, %{a} and %{b}" => "Hello, Foo and Bar"
+///
+/// // `locale` takes any `&str` expression and only applies to this call
+/// format_t!("greeting", locale = &user.locale); // => "Hallo Welt!"
 ///
 /// // With locale and variables
-/// format_t!("messages.hello", locale = "de", "Jason"); // messages.hello: "Hallo, {}" => "Hallo, Jason"
+/// format_t!("messages.hello", locale = "de", name = "Jason"); // messages.hello: "Hallo, %{name}" => "Hallo, Jason"
+/// ```
+///
+/// The key can also be an expression, which is looked up at runtime and
+/// falls back to the key itself if there is no translation.
+///
+/// ```ignore
+/// let key = "greeting";
+/// format_t!(key); // => "Hello world"
 /// ```
+///
+/// Translations are Ruby style templates, `%{name}` is replaced by the argument `name`
+/// and `%%` is a literal `%`. All other characters, including `{` and `}`, are kept as is.
 pub use rust_i18n_macro::format_t;
 
+/// Init translations for the current crate from a locale directory,
+/// relative to its `Cargo.toml`.
+///
+/// Must be called once at the crate root, before any `format_t!` of the crate.
+/// Each crate of a build can use its own locale directory.
+///
+/// Cargo only reruns `i18n!` for added locale files if a build script calls
+/// `rust_i18n_support::prepare` with the same directory, see the README.
+///
+/// ```ignore
+/// rust_i18n::i18n!("locales");
+/// ```
+pub use rust_i18n_macro::i18n;
+
+/// All items of a list translation, as `Vec<&'static str>`.
+///
+/// Lists are arrays in the locale files, their items are also translations
+/// of their own, keyed by index.
+///
+/// ```ignore
+/// // date.day_names: [Sunday, Monday, ...]
+/// list_t!("date.day_names"); // => vec!["Sunday", "Monday", ...]
+/// list_t!("date.day_names", locale = "de"); // => vec!["Sonntag", "Montag", ...]
+/// format_t!("date.day_names.1"); // => "Monday"
+/// ```
+///
+/// The key can be an expression too, an unknown key results in an empty list.
+pub use rust_i18n_macro::list_t;
+
+#[doc(hidden)]
+pub use rust_i18n_macro::__format_t;
+
+#[doc(hidden)]
+pub use rust_i18n_macro::__list_t;
+
+#[doc(hidden)]
+pub use rust_i18n_support::Catalog;
+
+/// A catalog registered by `i18n!`, its locales can be set.
+#[doc(hidden)]
+pub struct __RegisteredCatalog(pub &'static Catalog);
+
+inventory::collect!(__RegisteredCatalog);
+
+#[doc(hidden)]
+pub use inventory::submit as __submit;
+
+/// CLDR plural rules, used by `format_t!` to select between the `one`, `other`, ..
+/// forms of a translation by the `count` argument.
+pub use rust_i18n_support::plural;
+
+/// A BCP 47 language tag, canonicalised so `zh_CN`, `zh-cn` and `zh-CN` are the same locale.
+pub use rust_i18n_support::locale::{InvalidLocale, Locale};
+
+#[doc(hidden)]
+pub use rust_i18n_support::locale::canonicalize as __canonicalize_locale;
+
+#[cfg(feature = "async")]
+mod task;
+#[cfg(feature = "async")]
+pub use task::{scope_locale, ScopeLocale};
+
 static CURRENT_LOCALE: Lazy<Mutex<&'static str>> = Lazy::new(|| Mutex::new("en"));
 
-pub fn set_locale(locale: &str) {
-    let mut current_locale = CURRENT_LOCALE.lock().unwrap();
-    *current_locale = Box::leak::<'static>(Box::new(locale.to_owned()));
+thread_local! {
+    /// Overrides `CURRENT_LOCALE` on this thread, see [`LocaleGuard`].
+    static THREAD_LOCALE: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// A locale that cannot be set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocaleError {
+    /// Not a BCP 47 language tag.
+    Invalid(InvalidLocale),
+    /// Neither the locale nor its fallbacks are locales of any catalog of `i18n!`.
+    Unknown(Locale),
+}
+
+impl fmt::Display for LocaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(err) => err.fmt(f),
+            Self::Unknown(locale) => write!(
+                f,
+                "Unknown locale `{locale}`: neither it nor its fallbacks have translations"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LocaleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Invalid(err) => Some(err),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+impl From<InvalidLocale> for LocaleError {
+    fn from(err: InvalidLocale) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+/// The locale of the catalogs registered by `i18n!` to use for `locale`:
+/// itself if a catalog has it, else the first of its fallback chain one has.
+///
+/// Catalog locales are `&'static str` already, so nothing is allocated.
+fn resolve(locale: &str) -> Result<&'static str, LocaleError> {
+    let locale = Locale::parse(locale)?;
+    let catalogs = || inventory::iter::<__RegisteredCatalog>().map(|registered| registered.0);
+    catalogs()
+        .find_map(|catalog| {
+            let idx = catalog.locales.binary_search(&locale.as_str()).ok()?;
+            Some(catalog.locales[idx])
+        })
+        .or_else(|| catalogs().find_map(|catalog| catalog.resolve_locale(&locale)))
+        .ok_or(LocaleError::Unknown(locale))
+}
+
+/// Set the current locale, canonicalised, `zh_CN` becomes `zh-CN`.
+///
+/// A locale without translations is replaced by the first locale of its
+/// fallback chain with translations, `en-US` by `en` if there is no `en-US`.
+///
+/// Applies to all threads without an override by [`with_locale`] or [`LocaleGuard`].
+/// Fails if `locale` is not a BCP 47 language tag, or if neither it nor its
+/// fallbacks have translations in any crate of `i18n!`.
+pub fn set_locale(locale: &str) -> Result<(), LocaleError> {
+    let locale = resolve(locale)?;
+    *CURRENT_LOCALE.lock().unwrap() = locale;
+    Ok(())
 }
 
+/// The current locale, the override of the current thread if any,
+/// else the one set by [`set_locale`].
 pub fn locale() -> &'static str {
-    &CURRENT_LOCALE.lock().unwrap()
+    THREAD_LOCALE
+        .with(Cell::get)
+        .unwrap_or_else(|| *CURRENT_LOCALE.lock().unwrap())
 }
 
-#[macro_export]
-macro_rules! i18n {
-    ($path:literal) => {
-        // FIXME TODO
-    };
+/// Overrides the current locale on this thread until dropped.
+///
+/// The previous override is restored on drop, so guards can be nested.
+///
+/// ```ignore
+/// let _locale = rust_i18n::LocaleGuard::new("de")?;
+/// format_t!("hello"); // => "Hallo Welt!"
+/// ```
+#[must_use = "the override ends when the guard is dropped"]
+pub struct LocaleGuard {
+    previous: Option<&'static str>,
+    /// The guard restores a thread local, so it must stay on its thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LocaleGuard {
+    /// Override the current locale on this thread, resolved like by [`set_locale`].
+    ///
+    /// Fails like [`set_locale`].
+    pub fn new(locale: &str) -> Result<Self, LocaleError> {
+        Ok(Self::canonical(resolve(locale)?))
+    }
+
+    /// Override the current locale on this thread with an already canonical `locale`.
+    fn canonical(locale: &'static str) -> Self {
+        Self {
+            previous: THREAD_LOCALE.with(|current| current.replace(Some(locale))),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for LocaleGuard {
+    fn drop(&mut self) {
+        THREAD_LOCALE.with(|current| current.set(self.previous));
+    }
+}
+
+/// Run `f` with the current locale of this thread overridden by `locale`.
+///
+/// ```ignore
+/// let greeting = rust_i18n::with_locale("de", || format_t!("hello"))?; // => "Hallo Welt!"
+/// ```
+///
+/// Fails like [`set_locale`].
+pub fn with_locale<R>(locale: &str, f: impl FnOnce() -> R) -> Result<R, LocaleError> {
+    let _guard = LocaleGuard::new(locale)?;
+    Ok(f())
 }
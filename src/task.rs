@@ -0,0 +1,44 @@
+//! Locales attached to a future rather than a thread, for async runtimes
+//! that move tasks between threads.
+
+use crate::{resolve, LocaleError, LocaleGuard};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A future with the current locale overridden while it is polled, see [`scope_locale`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ScopeLocale<F> {
+    locale: &'static str,
+    future: Pin<Box<F>>,
+}
+
+/// Override the current locale with `locale` whenever `future` is polled,
+/// so `locale()` and `format_t!` use it across `.await` points, on whatever
+/// thread the task runs.
+///
+/// ```ignore
+/// async fn handle(request: Request) -> Response {
+///     rust_i18n::scope_locale(&request.locale, async {
+///         render().await // `format_t!` uses the request's locale
+///     })?
+///     .await
+/// }
+/// ```
+///
+/// Fails like [`set_locale`](crate::set_locale).
+pub fn scope_locale<F: Future>(locale: &str, future: F) -> Result<ScopeLocale<F>, LocaleError> {
+    Ok(ScopeLocale {
+        locale: resolve(locale)?,
+        future: Box::pin(future),
+    })
+}
+
+impl<F: Future> Future for ScopeLocale<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let _guard = LocaleGuard::canonical(self.locale);
+        self.future.as_mut().poll(cx)
+    }
+}
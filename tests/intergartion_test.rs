@@ -3,15 +3,17 @@ rust_i18n::i18n!("./tests/locales");
 #[cfg(test)]
 mod tests {
     use rust_i18n::format_t;
-    use rust_i18n::locale;
 
     #[test]
     fn it_foo_title() {
-        assert_eq!(format_t!("hello"), "Foo - Hello, World!");
+        // `foo` uses the translations of its own locale directory.
+        assert_eq!(foo::f(), "Foo - Hello, World!");
     }
 
     #[test]
+    #[allow(deprecated)] // unfilled placeholders are reported as deprecation warnings
     fn it_t() {
+        let _locale = rust_i18n::LocaleGuard::new("en").unwrap();
         assert_eq!(format_t!("hello"), "Bar - Hello, World!");
 
         // Vars
@@ -28,10 +30,9 @@ mod tests {
             "Hello, Jason. Your message is: Bla bla"
         );
 
-        rust_i18n::set_locale("de");
-        assert_eq!(format_t!("messages.hello", name = "world"), "Hallo, world!");
+        let greeting = rust_i18n::with_locale("de", || format_t!("messages.hello", name = "world"));
+        assert_eq!(greeting.unwrap(), "Hallo, world!");
 
-        rust_i18n::set_locale("en");
         assert_eq!(format_t!("messages.hello", name = "world"), "Hello, world!");
     }
 
@@ -40,7 +41,7 @@ mod tests {
         assert_eq!(format_t!("hello", locale = "de"), "Bar - Hallo Welt!");
         assert_eq!(format_t!("hello", locale = "en"), "Bar - Hello, World!");
 
-        rust_i18n::set_locale("en");
+        let _locale = rust_i18n::LocaleGuard::new("en").unwrap();
         assert_eq!(format_t!("messages.hello", name = "Jason"), "Hello, Jason!");
         assert_eq!(
             format_t!("messages.hello", locale = "en", name = "Jason"),
@@ -54,18 +55,41 @@ mod tests {
             format_t!("messages.hello", locale = "de", name = "Jason"),
             "Hallo, Jason!"
         );
+
+        // Locales are canonicalised.
+        assert_eq!(format_t!("hello", locale = "DE"), "Bar - Hallo Welt!");
+        assert_eq!(format_t!("hello", locale = "de_at"), "Bar - Hallo Welt!");
+        let key = "hello";
+        assert_eq!(format_t!(key, locale = "DE"), "Bar - Hallo Welt!");
+        assert!(matches!(
+            rust_i18n::set_locale("not a locale"),
+            Err(rust_i18n::LocaleError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn it_pluralizes_by_count() {
+        let _locale = rust_i18n::LocaleGuard::new("en").unwrap();
+        assert_eq!(format_t!("messages", count = 0), "You have no messages.");
+        assert_eq!(format_t!("messages", count = 1), "You have one message.");
+        assert_eq!(format_t!("messages", count = 3), "You have 3 messages.");
+        assert_eq!(
+            format_t!("messages", count = "1.0"),
+            "You have 1.0 messages."
+        );
     }
 
     #[test]
     fn it_with_merge_file() {
-        rust_i18n::set_locale("en");
+        let _locale = rust_i18n::LocaleGuard::new("en").unwrap();
         assert_eq!(format_t!("user.title"), "User Title");
         assert_eq!(format_t!("messages.user.title"), "Message User Title");
     }
 
     #[test]
+    #[allow(deprecated)] // unfilled placeholders are reported as deprecation warnings
     fn it_support_expr() {
-        rust_i18n::set_locale("en");
+        let _locale = rust_i18n::LocaleGuard::new("en").unwrap();
         let name = "Jason Lee";
         let locale = "en";
 
@@ -100,4 +124,90 @@ mod tests {
             "Hello, Jason Lee!"
         );
     }
+
+    #[test]
+    fn it_overrides_the_locale_per_thread() {
+        // No other test changes the global locale.
+        rust_i18n::set_locale("de").unwrap();
+        // There are no `en-US` translations, `en` is used instead.
+        let _locale = rust_i18n::LocaleGuard::new("en_us").unwrap();
+        assert_eq!(rust_i18n::locale(), "en");
+        assert_eq!(format_t!("hello"), "Bar - Hello, World!");
+
+        std::thread::spawn(|| {
+            assert_eq!(format_t!("hello"), "Bar - Hallo Welt!");
+            let greeting = rust_i18n::with_locale("en", || {
+                let nested = rust_i18n::with_locale("de", || format_t!("hello")).unwrap();
+                assert_eq!(nested, "Bar - Hallo Welt!");
+                format_t!("hello")
+            });
+            assert_eq!(greeting.unwrap(), "Bar - Hello, World!");
+            assert_eq!(rust_i18n::locale(), "de");
+        })
+        .join()
+        .unwrap();
+
+        assert!(rust_i18n::with_locale("not a locale", || ()).is_err());
+        assert_eq!(rust_i18n::locale(), "en");
+
+        // Locales are those of the catalogs, nothing is allocated.
+        let en = rust_i18n::with_locale("EN", rust_i18n::locale).unwrap();
+        assert!(std::ptr::eq(en, rust_i18n::locale()));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn it_scopes_the_locale_to_a_future() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct NoopWaker;
+
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        /// Pending on the first poll, like waiting for I/O.
+        struct YieldNow(bool);
+
+        impl Future for YieldNow {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+                if std::mem::replace(&mut self.0, true) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+
+        async fn greet() -> String {
+            YieldNow(false).await;
+            format_t!("hello")
+        }
+
+        let _locale = rust_i18n::LocaleGuard::new("en-US").unwrap();
+        let mut de = Box::pin(rust_i18n::scope_locale("de", greet()).unwrap());
+        let mut en = Box::pin(rust_i18n::scope_locale("en", greet()).unwrap());
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        // Interleave both tasks on a single thread.
+        assert!(de.as_mut().poll(&mut cx).is_pending());
+        assert!(en.as_mut().poll(&mut cx).is_pending());
+        // The thread's locale is restored between polls.
+        assert_eq!(rust_i18n::locale(), "en");
+        assert_eq!(
+            de.as_mut().poll(&mut cx),
+            Poll::Ready("Bar - Hallo Welt!".to_owned())
+        );
+        assert_eq!(
+            en.as_mut().poll(&mut cx),
+            Poll::Ready("Bar - Hello, World!".to_owned())
+        );
+        assert!(rust_i18n::scope_locale("not a locale", greet()).is_err());
+    }
 }
@@ -0,0 +1,32 @@
+//! Only locales with translations can be set, so setting arbitrary tags,
+//! like those of user input, cannot make real locales fail.
+
+rust_i18n::i18n!("./tests/locales");
+
+use rust_i18n::LocaleError;
+
+#[test]
+fn it_only_sets_locales_with_translations() {
+    for idx in 0..1000 {
+        let err = rust_i18n::set_locale(&format!("x-{idx}")).unwrap_err();
+        assert!(matches!(err, LocaleError::Unknown(_)));
+    }
+
+    let err = rust_i18n::set_locale("fr").unwrap_err();
+    assert!(matches!(&err, LocaleError::Unknown(locale) if locale == "fr"));
+    assert_eq!(
+        err.to_string(),
+        "Unknown locale `fr`: neither it nor its fallbacks have translations"
+    );
+    assert!(rust_i18n::with_locale("fr", || ()).is_err());
+    assert_eq!(rust_i18n::locale(), "en");
+
+    rust_i18n::set_locale("DE").unwrap();
+    assert_eq!(rust_i18n::locale(), "de");
+    rust_i18n::set_locale("de-CH").unwrap();
+    assert_eq!(rust_i18n::locale(), "de");
+    assert_eq!(
+        rust_i18n::with_locale("en-GB", rust_i18n::locale).unwrap(),
+        "en"
+    );
+}